@@ -0,0 +1,116 @@
+use amethyst::core::Time;
+use amethyst::{GameData, SimpleState, StateData, StateEvent};
+use log::info;
+
+use crate::autopilot::Pilot;
+use crate::collision::ShipCrashed;
+use crate::controls;
+use crate::game::GameState;
+
+/// How long the menu has to sit idle before the demo starts, in seconds.
+const IDLE_SECONDS: f32 = 15.0;
+
+/// The attract mode: once a menu has been left alone for a while, the autopilot plays runs
+/// behind it until the player does anything.
+///
+/// This isn't a state of its own, since the menu has to keep working on top of it; the menu's
+/// state owns one and forwards its events and updates to it, like it does for its `Menu`.
+#[derive(Default)]
+pub struct AttractMode {
+    idle_seconds: f32,
+    demo: Option<Demo>,
+    /// Set when the player just stopped the demo, so the rest of that input is ignored too
+    /// (a key press also comes through as an action press, for example).
+    waking: bool,
+}
+
+/// A run being played for the attract mode.
+struct Demo {
+    game: GameState,
+    /// Who was flying before the demo took over, to hand the ship back to.
+    pilot: Pilot,
+}
+
+impl AttractMode {
+    pub fn is_playing(&self) -> bool {
+        self.demo.is_some()
+    }
+
+    /// Stops the demo (if there is one) and starts counting idle time from zero again.
+    pub fn stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.idle_seconds = 0.0;
+        if let Some(mut demo) = self.demo.take() {
+            demo.game
+                .on_stop(StateData::new(state_data.world, state_data.data));
+            state_data.world.add_resource(demo.pilot);
+        }
+    }
+
+    /// Call with every event the menu gets. Returns `true` if the event stopped the demo (or
+    /// came in along with the input that did), in which case the menu should ignore it.
+    pub fn handle_event(
+        &mut self,
+        state_data: StateData<'_, GameData<'_, '_>>,
+        event: &StateEvent,
+    ) -> bool {
+        if self.waking {
+            return true;
+        }
+        if !controls::is_any_input(event) {
+            return false;
+        }
+
+        self.idle_seconds = 0.0;
+        if !self.is_playing() {
+            return false;
+        }
+
+        info!("Stopping the demo");
+        self.stop(state_data);
+        self.waking = true;
+        true
+    }
+
+    /// Call every frame. Counts idle time, starts the demo once the menu has been idle long
+    /// enough, and keeps it playing, starting a new run whenever the autopilot crashes.
+    pub fn update(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.waking = false;
+
+        match &mut self.demo {
+            Some(demo) => {
+                demo.game.run_simulation(state_data.world);
+                if state_data.world.read_resource::<ShipCrashed>().0 {
+                    demo.game
+                        .on_stop(StateData::new(state_data.world, state_data.data));
+                    demo.game = start_run(state_data);
+                }
+            }
+            None => {
+                self.idle_seconds += state_data
+                    .world
+                    .read_resource::<Time>()
+                    .delta_real_seconds();
+                if self.idle_seconds >= IDLE_SECONDS {
+                    info!("The menu has been idle for a while, starting the demo");
+                    let pilot = *state_data.world.read_resource::<Pilot>();
+                    state_data.world.add_resource(Pilot::Autopilot);
+                    self.demo = Some(Demo {
+                        game: start_run(state_data),
+                        pilot,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Starts a new run on a random seed, set up to play behind a menu: no HUD, no ghost, and no
+/// game-over screen.
+fn start_run(state_data: StateData<'_, GameData<'_, '_>>) -> GameState {
+    let mut game = GameState::new(rand::random())
+        .without_hud()
+        .without_ghost()
+        .without_game_over();
+    game.on_start(state_data);
+    game
+}
@@ -0,0 +1,268 @@
+use amethyst::core::Transform;
+use amethyst::ecs::{Join, Read, ReadStorage, System, Write};
+use serde::{Deserialize, Serialize};
+
+use crate::collision::Collider;
+use crate::cube_field::Cube;
+use crate::difficulty::Difficulty;
+use crate::gameplay_config::GameplayConfig;
+use crate::ship::{Ship, ShipConfig};
+
+/// Who steers the ship (unless a replay is playing).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pilot {
+    Player,
+    /// `AutopilotSystem` does, for soak tests and demos.
+    Autopilot,
+}
+
+impl Default for Pilot {
+    fn default() -> Self {
+        Pilot::Player
+    }
+}
+
+/// How the autopilot flies, loaded as part of `GameplayConfig`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AutopilotConfig {
+    /// How far ahead of the ship it looks for cubes.
+    pub look_ahead: f32,
+    /// How far apart the lanes it picks between are, sideways.
+    pub lane_spacing: f32,
+    /// How far to either side of the ship it looks for a better lane.
+    pub search_width: f32,
+    /// Extra room it tries to keep between the ship and the sides of a cube.
+    pub margin: f32,
+    /// How much clear distance ahead it gives up to avoid strafing one unit sideways. Higher
+    /// values make it stick to its lane more.
+    pub lane_change_cost: f32,
+}
+
+impl Default for AutopilotConfig {
+    fn default() -> Self {
+        AutopilotConfig {
+            look_ahead: 60.0,
+            lane_spacing: 0.5,
+            search_width: 12.0,
+            margin: 0.3,
+            lane_change_cost: 0.5,
+        }
+    }
+}
+
+/// The steering the autopilot wants this tick, from `-1.0` (full left) to `1.0` (full right).
+/// `SteeringSystem` steers with this instead of the player's input while the `Pilot` is the
+/// autopilot, so autopilot runs are recorded like any other.
+#[derive(Default)]
+pub struct AutopilotSteering(pub f32);
+
+/// Flies the ship for the autopilot.
+///
+/// Every tick it looks at the lanes around the ship and heads for the one that stays clear the
+/// longest, counting the cubes in the way of getting there. It only picks lanes inside the cube
+/// field that the ship can reach.
+pub struct AutopilotSystem;
+
+impl<'s> System<'s> for AutopilotSystem {
+    type SystemData = (
+        Write<'s, AutopilotSteering>,
+        Read<'s, Pilot>,
+        Read<'s, GameplayConfig>,
+        Read<'s, Difficulty>,
+        ReadStorage<'s, Ship>,
+        ReadStorage<'s, Cube>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Transform>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut steering,
+            pilot,
+            config,
+            difficulty,
+            ships,
+            cubes,
+            colliders,
+            transforms,
+        ): Self::SystemData,
+    ) {
+        if *pilot != Pilot::Autopilot {
+            return;
+        }
+
+        let (_, ship_collider, ship_transform) =
+            match (&ships, &colliders, &transforms).join().next() {
+                Some(ship) => ship,
+                None => return,
+            };
+
+        let ship_x = ship_transform.translation().x;
+        let ship_z = ship_transform.translation().z;
+        let autopilot = &config.autopilot;
+
+        // Every cube that's still ahead of the ship, as how far ahead it is and where it is
+        // sideways (and how far sideways it reaches, including the ship's width)
+        let ahead: Vec<(f32, f32, f32)> = (&cubes, &colliders, &transforms)
+            .join()
+            .filter_map(|(_, collider, transform)| {
+                let reach_z = collider.half_extents.z + ship_collider.half_extents.z;
+                let distance = ship_z - transform.translation().z;
+                if distance < -reach_z || distance > autopilot.look_ahead {
+                    return None;
+                }
+
+                let reach_x =
+                    collider.half_extents.x + ship_collider.half_extents.x + autopilot.margin;
+                Some((
+                    (distance - reach_z).max(0.0),
+                    transform.translation().x,
+                    reach_x,
+                ))
+            })
+            .collect();
+
+        let speed = difficulty.speed(&config.cube_field);
+        let ship_config = &config.ship;
+        let reach = difficulty.spread(&config.cube_field).min(ship_config.max_x);
+
+        let lanes = (autopilot.search_width / autopilot.lane_spacing.max(0.01)) as i32;
+        let mut target = ship_x;
+        let mut best_score = std::f32::MIN;
+        for lane in -lanes..=lanes {
+            let lane_x = ship_x + lane as f32 * autopilot.lane_spacing;
+            if lane_x.abs() > reach {
+                continue;
+            }
+
+            // Roughly how long strafing over to the lane takes, and how far the field scrolls
+            // in that time; any cube between here and there, that close, is in the way
+            let strafe = (lane_x - ship_x).abs();
+            let strafe_seconds =
+                strafe / ship_config.max_speed + ship_config.max_speed / ship_config.acceleration;
+            let strafe_distance = speed * strafe_seconds;
+            let (left, right) = (ship_x.min(lane_x), ship_x.max(lane_x));
+
+            let clear = ahead
+                .iter()
+                .filter(|(distance, x, reach_x)| {
+                    let in_lane = (x - lane_x).abs() < *reach_x;
+                    let on_the_way =
+                        *distance < strafe_distance && *x > left - reach_x && *x < right + reach_x;
+                    in_lane || on_the_way
+                })
+                .map(|(distance, _, _)| *distance)
+                .fold(autopilot.look_ahead, f32::min);
+
+            let score = clear - strafe * autopilot.lane_change_cost;
+            if score > best_score {
+                best_score = score;
+                target = lane_x;
+            }
+        }
+
+        steering.0 = steer_towards(target - ship_x, ship_config);
+    }
+}
+
+/// Steering that gets the ship `offset` units sideways as quickly as it can without
+/// overshooting: full speed towards the target, easing off just in time to stop on it.
+///
+/// Steering sets the velocity the ship accelerates towards, so this is the fastest the ship can
+/// be going at `offset` units out and still stop in time.
+fn steer_towards(offset: f32, config: &ShipConfig) -> f32 {
+    let stopping_speed = (2.0 * config.acceleration * offset.abs()).sqrt();
+    let target_velocity = stopping_speed.min(config.max_speed) * offset.signum();
+    (target_velocity / config.max_speed).max(-1.0).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::gameplay_config;
+    use crate::test_support::TestRun;
+
+    /// Least distance the autopilot has to average on every stage, for the game to count as
+    /// beatable.
+    const MIN_AVERAGE: f32 = 200.0;
+
+    /// Lets the autopilot play `runs` runs (on seeds 1 to `runs`) of up to `max_ticks` ticks at
+    /// every stage of the shipped difficulty curve, and returns how far it got on average at
+    /// each one.
+    fn soak(runs: u64, max_ticks: u64) -> Vec<(String, f32)> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join("config")
+            .join("gameplay.ron");
+        let config = gameplay_config::load(&path);
+
+        config
+            .difficulty
+            .stages
+            .iter()
+            .map(|stage| {
+                let mut held = config.clone();
+                held.difficulty
+                    .hold_stage(&stage.name)
+                    .expect("A stage from the config is missing");
+
+                let total: f32 = (1..=runs)
+                    .map(|seed| {
+                        let mut run = TestRun::new(held.clone(), seed, Pilot::Autopilot);
+                        run.run(max_ticks);
+                        run.score()
+                    })
+                    .sum();
+                let average = total / runs as f32;
+                println!(
+                    "{}: {:.1} units on average over {} runs",
+                    stage.name, average, runs
+                );
+                (stage.name.clone(), average)
+            })
+            .collect()
+    }
+
+    fn assert_beatable(averages: &[(String, f32)]) {
+        let failures: Vec<String> = averages
+            .iter()
+            .filter(|(_, average)| *average < MIN_AVERAGE)
+            .map(|(stage, average)| format!("{} ({:.1})", stage, average))
+            .collect();
+        assert!(
+            failures.is_empty(),
+            "The autopilot averaged under {} units on: {}",
+            MIN_AVERAGE,
+            failures.join(", ")
+        );
+    }
+
+    #[test]
+    fn autopilot_gets_somewhere_on_every_stage() {
+        // A couple of runs of half a minute each, to keep `cargo test` quick
+        assert_beatable(&soak(2, 1_800));
+    }
+
+    /// The full soak test: ten runs of up to ten minutes each per stage. Run it after tuning
+    /// changes, with `cargo test --release -- --ignored --nocapture soak`.
+    #[test]
+    #[ignore]
+    fn soak_every_stage() {
+        assert_beatable(&soak(10, 36_000));
+    }
+
+    #[test]
+    fn steering_eases_off_before_the_target() {
+        let config = ShipConfig::default();
+        assert_eq!(steer_towards(0.0, &config), 0.0);
+        assert_eq!(steer_towards(100.0, &config), 1.0);
+        assert_eq!(steer_towards(-100.0, &config), -1.0);
+
+        let close = steer_towards(0.1, &config);
+        assert!(close > 0.0 && close < 1.0);
+    }
+}
@@ -0,0 +1,159 @@
+use amethyst::core::math::Vector3;
+use amethyst::core::{Time, Transform};
+use amethyst::ecs::{
+    Component, DenseVecStorage, Entities, Entity, Join, Read, ReadStorage, System, World,
+    WriteStorage,
+};
+use amethyst::prelude::Builder;
+use amethyst::renderer::Camera;
+use amethyst::window::ScreenDimensions;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay_config::GameplayConfig;
+use crate::ship::Ship;
+
+/// Longest step the camera's spring is moved by at once, in seconds.
+const MAX_SPRING_STEP: f32 = 1.0 / 120.0;
+
+/// Most spring steps a single frame gets; any time beyond that is dropped.
+const MAX_SPRING_STEPS: u32 = 12;
+
+/// How the camera follows the ship, loaded as part of `GameplayConfig`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FollowCameraConfig {
+    /// Where the camera sits relative to the ship, as `(x, y, z)`. Positive Y is above the ship,
+    /// and positive Z is behind it.
+    pub offset: [f32; 3],
+    /// How strongly the camera is pulled towards where it should be. Higher is snappier.
+    pub stiffness: f32,
+    /// How much the camera's movement is slowed down. Too little and it overshoots and wobbles;
+    /// `2 * sqrt(stiffness)` settles as fast as possible without overshooting.
+    pub damping: f32,
+    /// How much of the ship's bank the camera rolls along with (`0.0` is none, `1.0` is all).
+    pub roll_factor: f32,
+}
+
+impl Default for FollowCameraConfig {
+    fn default() -> Self {
+        FollowCameraConfig {
+            offset: [0.0, 3.0, 8.0],
+            stiffness: 40.0,
+            damping: 12.0,
+            roll_factor: 0.25,
+        }
+    }
+}
+
+impl FollowCameraConfig {
+    fn offset(&self) -> Vector3<f32> {
+        Vector3::new(self.offset[0], self.offset[1], self.offset[2])
+    }
+}
+
+/// A camera that chases `target` around, on a spring.
+pub struct FollowCamera {
+    pub target: Entity,
+    /// The camera's current velocity, in units per second.
+    pub velocity: Vector3<f32>,
+}
+
+impl Component for FollowCamera {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Creates a camera that follows `ship`, starting out exactly where it should be.
+pub fn initialize_camera(world: &mut World, ship: Entity) -> Entity {
+    // Match the camera's aspect ratio to the window, whatever size `config/display.ron` made it
+    let (width, height) = {
+        let dimensions = world.read_resource::<ScreenDimensions>();
+        (dimensions.width(), dimensions.height())
+    };
+
+    let ship_position = *world
+        .read_storage::<Transform>()
+        .get(ship)
+        .expect("The ship needs a Transform before the camera can follow it")
+        .translation();
+    let offset = world.read_resource::<GameplayConfig>().camera.offset();
+
+    let mut transform = Transform::default();
+    transform.set_translation(ship_position + offset);
+    transform.set_rotation_euler(pitch_towards_target(&offset), 0.0, 0.0);
+
+    world
+        .create_entity()
+        .with(Camera::standard_3d(width, height))
+        .with(FollowCamera {
+            target: ship,
+            velocity: Vector3::zeros(),
+        })
+        .with(transform)
+        .build()
+}
+
+/// Keeps each `FollowCamera` behind and above its target, with spring/damper smoothing, and
+/// rolls it a little along with the ship's banking.
+pub struct FollowCameraSystem;
+
+impl<'s> System<'s> for FollowCameraSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, FollowCamera>,
+        WriteStorage<'s, Transform>,
+        ReadStorage<'s, Ship>,
+        Read<'s, GameplayConfig>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut cameras, mut transforms, ships, config, time): Self::SystemData,
+    ) {
+        let camera_config = &config.camera;
+        let offset = camera_config.offset();
+        let delta_seconds = time.delta_seconds();
+
+        for (camera_entity, camera) in (&entities, &mut cameras).join() {
+            let target_position = match transforms.get(camera.target) {
+                Some(transform) => *transform.translation(),
+                None => continue,
+            };
+
+            // The roll follows the ship's bank, which comes from its sideways velocity
+            let bank = ships
+                .get(camera.target)
+                .map_or(0.0, |ship| ship.bank(&config.ship));
+
+            let transform = match transforms.get_mut(camera_entity) {
+                Some(transform) => transform,
+                None => continue,
+            };
+
+            // Explicit integration blows up if a step is too long for the spring, so long
+            // frames are split into steps no longer than `MAX_SPRING_STEP`, and frames that
+            // are too long even for that (say, after the window was dragged) are cut short
+            let frame_seconds = delta_seconds.min(MAX_SPRING_STEP * MAX_SPRING_STEPS as f32);
+            let steps = (frame_seconds / MAX_SPRING_STEP).ceil().max(1.0);
+            let step_seconds = frame_seconds / steps;
+            for _ in 0..steps as u32 {
+                let displacement = target_position + offset - transform.translation();
+                let acceleration = displacement * camera_config.stiffness
+                    - camera.velocity * camera_config.damping;
+                camera.velocity += acceleration * step_seconds;
+                transform.prepend_translation(camera.velocity * step_seconds);
+            }
+
+            transform.set_rotation_euler(
+                pitch_towards_target(&offset),
+                0.0,
+                bank * camera_config.roll_factor,
+            );
+        }
+    }
+}
+
+/// How far to tilt the camera down (around the X axis) so it looks at the target from `offset`.
+fn pitch_towards_target(offset: &Vector3<f32>) -> f32 {
+    -offset.y.atan2(offset.z)
+}
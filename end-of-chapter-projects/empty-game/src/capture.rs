@@ -0,0 +1,319 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use amethyst::shrev::EventChannel;
+use amethyst::window::DisplayConfig;
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans, TransEvent};
+use log::{error, info};
+
+use crate::game::GameState;
+use crate::offscreen::FrameReadback;
+
+/// Seed for `--capture` runs when `--seed` isn't given; captures have to be reproducible, so
+/// they never pick a random one.
+pub const DEFAULT_SEED: u64 = 1;
+
+/// How many frames a `--capture` run plays before taking the screenshot, if `--frames` isn't
+/// given.
+pub const DEFAULT_FRAMES: u64 = 120;
+
+/// Size of the captured image, if `config/display.ron` doesn't give one.
+const DEFAULT_DIMENSIONS: (u32, u32) = (1024, 768);
+
+/// What `--capture` should render, and where to put it.
+pub struct CaptureOptions {
+    pub path: PathBuf,
+    pub frames: u64,
+    pub seed: u64,
+}
+
+/// How big a captured image is: the size the window would be.
+pub fn dimensions(display_config: &DisplayConfig) -> (u32, u32) {
+    display_config.dimensions.unwrap_or(DEFAULT_DIMENSIONS)
+}
+
+/// Sits at the bottom of the state stack for `--capture` runs, the same way `HeadlessState`
+/// does for `--headless` runs, except the run is drawn, into an image rather than a window (see
+/// `RenderToImage`).
+///
+/// Captures have to look the same every time, so the autopilot flies and there's no ghost; and
+/// if the ship crashes anyway, the run just ends rather than showing the game-over screen. Once
+/// `frames` frames have been simulated, the next frame drawn is saved as a PNG, and the game
+/// quits.
+pub struct CaptureState {
+    options: CaptureOptions,
+    frames_run: u64,
+    started: bool,
+    captured: Arc<AtomicBool>,
+}
+
+impl CaptureState {
+    /// `captured` is shared with `main()`, so it can tell whether the frame was saved after
+    /// `Application::run` returns.
+    pub fn new(options: CaptureOptions, captured: Arc<AtomicBool>) -> Self {
+        CaptureState {
+            options,
+            frames_run: 0,
+            started: false,
+            captured,
+        }
+    }
+}
+
+impl SimpleState for CaptureState {
+    fn update(&mut self, _state_data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        // Back on top means the run ended before the frame was captured
+        if self.started {
+            return Trans::Quit;
+        }
+
+        self.started = true;
+        Trans::Push(Box::new(
+            GameState::new(self.options.seed)
+                .without_ghost()
+                .without_game_over(),
+        ))
+    }
+
+    fn shadow_update(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if !self.started {
+            return;
+        }
+
+        self.frames_run += 1;
+        let world = state_data.world;
+
+        // The frame is read back while it's drawn, which is during the next frame's update
+        if self.frames_run == self.options.frames {
+            world.write_resource::<FrameReadback>().request();
+            return;
+        }
+        if self.frames_run < self.options.frames {
+            return;
+        }
+
+        match world.write_resource::<FrameReadback>().take() {
+            Some(frame) => match frame.save(&self.options.path) {
+                Ok(()) => {
+                    info!(
+                        "Captured frame {} to {:?}",
+                        self.frames_run, self.options.path
+                    );
+                    self.captured.store(true, Ordering::SeqCst);
+                }
+                Err(error) => error!("Couldn't save the capture: {}", error),
+            },
+            None => error!("The renderer didn't read the frame back"),
+        }
+
+        world
+            .write_resource::<EventChannel<TransEvent<GameData<'static, 'static>, StateEvent>>>()
+            .single_write(Box::new(|| Trans::Quit));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use amethyst::config::Config;
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::display::{self, RenderConfig};
+    use crate::{controls, gameplay_config};
+
+    /// How far apart (0-255) a single color channel can be before the pixel counts as
+    /// different. Software renderers aren't bit-exact across driver versions, so this is never
+    /// zero.
+    const CHANNEL_TOLERANCE: u8 = 8;
+
+    /// Fraction of pixels that may differ before a capture no longer matches its golden image.
+    const MAX_DIFFERING_FRACTION: f64 = 0.001;
+
+    /// The scenes in `tests/golden/`, as `(seed, frames)`.
+    const SCENES: &[(u64, u64)] = &[(1, 60), (1, 300), (42, 600)];
+
+    /// How a capture differs from its golden image.
+    #[derive(Debug)]
+    struct ImageDiff {
+        /// Pixels where at least one channel is further apart than the tolerance.
+        differing_pixels: u64,
+        total_pixels: u64,
+        /// The biggest difference in any one channel of any pixel.
+        max_channel_difference: u8,
+    }
+
+    impl ImageDiff {
+        /// Whether few enough pixels differ for the images to count as the same.
+        fn matches(&self) -> bool {
+            self.differing_pixels as f64 <= self.total_pixels as f64 * MAX_DIFFERING_FRACTION
+        }
+    }
+
+    /// Compares two images of the same size pixel by pixel.
+    fn compare_images(actual: &RgbaImage, golden: &RgbaImage, channel_tolerance: u8) -> ImageDiff {
+        assert_eq!(
+            actual.dimensions(),
+            golden.dimensions(),
+            "The capture and the golden image are different sizes"
+        );
+
+        let mut diff = ImageDiff {
+            differing_pixels: 0,
+            total_pixels: u64::from(actual.width()) * u64::from(actual.height()),
+            max_channel_difference: 0,
+        };
+        for (actual, golden) in actual.pixels().zip(golden.pixels()) {
+            let difference = actual
+                .0
+                .iter()
+                .zip(golden.0.iter())
+                .map(|(a, g)| if a > g { a - g } else { g - a })
+                .max()
+                .unwrap_or(0);
+            diff.max_channel_difference = diff.max_channel_difference.max(difference);
+            if difference > channel_tolerance {
+                diff.differing_pixels += 1;
+            }
+        }
+
+        diff
+    }
+
+    /// A 100x100 image, all one gray.
+    fn gray(level: u8) -> RgbaImage {
+        RgbaImage::from_pixel(100, 100, image::Rgba([level, level, level, 255]))
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let diff = compare_images(&gray(100), &gray(100), CHANNEL_TOLERANCE);
+        assert_eq!(diff.differing_pixels, 0);
+        assert_eq!(diff.total_pixels, 10_000);
+        assert_eq!(diff.max_channel_difference, 0);
+        assert!(diff.matches());
+    }
+
+    #[test]
+    fn differences_within_the_tolerance_dont_count() {
+        let diff = compare_images(
+            &gray(100),
+            &gray(100 + CHANNEL_TOLERANCE),
+            CHANNEL_TOLERANCE,
+        );
+        assert_eq!(diff.differing_pixels, 0);
+        assert_eq!(diff.max_channel_difference, CHANNEL_TOLERANCE);
+        assert!(diff.matches());
+
+        let diff = compare_images(
+            &gray(100 + CHANNEL_TOLERANCE + 1),
+            &gray(100),
+            CHANNEL_TOLERANCE,
+        );
+        assert_eq!(diff.differing_pixels, 10_000);
+        assert!(!diff.matches());
+    }
+
+    #[test]
+    fn a_few_differing_pixels_still_match() {
+        let golden = gray(100);
+        let mut actual = golden.clone();
+
+        // 0.1% of 10,000 pixels is 10
+        for x in 0..10 {
+            actual.put_pixel(x, 0, image::Rgba([255, 0, 0, 255]));
+        }
+        let diff = compare_images(&actual, &golden, CHANNEL_TOLERANCE);
+        assert_eq!(diff.differing_pixels, 10);
+        assert_eq!(diff.max_channel_difference, 155);
+        assert!(diff.matches());
+
+        actual.put_pixel(10, 0, image::Rgba([255, 0, 0, 255]));
+        assert!(!compare_images(&actual, &golden, CHANNEL_TOLERANCE).matches());
+    }
+
+    /// Plays `seed` for `frames` frames with the shipped configs, and captures it to `path`.
+    fn capture(seed: u64, frames: u64, path: &Path) -> bool {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config_dir = root.join("config");
+        let assets_dir = root.join("assets");
+        let gameplay_config_path = assets_dir.join("config").join("gameplay.ron");
+
+        let display_config = display::load_display_config(&config_dir.join("display.ron"));
+
+        crate::run_capture(
+            assets_dir,
+            controls::load_bindings(&config_dir.join("bindings.ron"), None).unwrap(),
+            gameplay_config::load(&gameplay_config_path),
+            dimensions(&display_config),
+            &RenderConfig::load(config_dir.join("render.ron")),
+            CaptureOptions {
+                path: path.to_path_buf(),
+                frames,
+                seed,
+            },
+        )
+        .unwrap()
+    }
+
+    /// Renders every scene and compares it with its golden image in `tests/golden/`. It needs
+    /// a Vulkan driver, so it's left out of a plain `cargo test`; run it with
+    /// `cargo test -- --ignored golden`. After an intentional rendering change, run it with
+    /// `BLESS=1` to replace the golden images with fresh captures instead, and look over the new
+    /// images before committing them.
+    #[test]
+    #[ignore]
+    fn captures_match_golden_images() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let golden_dir = root.join("tests").join("golden");
+        let output_dir = root.join("target").join("captures");
+        fs::create_dir_all(&output_dir).unwrap();
+        let bless = env::var_os("BLESS").is_some();
+
+        let mut failures = Vec::new();
+        for &(seed, frames) in SCENES {
+            let name = format!("seed-{}-frames-{}.png", seed, frames);
+            let path = output_dir.join(&name);
+            assert!(
+                capture(seed, frames, &path),
+                "The run on seed {} ended before frame {} was captured",
+                seed,
+                frames
+            );
+
+            let golden = golden_dir.join(&name);
+            if bless {
+                fs::copy(&path, &golden).unwrap();
+                continue;
+            }
+
+            if !golden.exists() {
+                failures.push(format!(
+                    "{} (no golden image; run with BLESS=1 to create it)",
+                    name
+                ));
+                continue;
+            }
+
+            let actual = image::open(&path).unwrap().to_rgba();
+            let expected = image::open(&golden)
+                .unwrap_or_else(|error| panic!("Couldn't open {:?}: {}", golden, error))
+                .to_rgba();
+            let diff = compare_images(&actual, &expected, CHANNEL_TOLERANCE);
+            if !diff.matches() {
+                failures.push(format!("{} ({:?})", name, diff));
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "Captures in {:?} don't match their golden images: {}",
+            output_dir,
+            failures.join(", ")
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::PathBuf;
+
+use log::LevelFilter;
+use structopt::clap::{Error, ErrorKind};
+use structopt::StructOpt;
+
+use crate::display::RenderPipeline;
+
+/// Steer your ship through an endless field of cubes.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "cubefield")]
+pub struct Args {
+    /// Seed for the cube field; the same seed always gives the same run. Overrides
+    /// `config/rng.ron`
+    #[structopt(long, conflicts_with = "replay")]
+    pub seed: Option<u64>,
+
+    /// Play a run without a window (or a GPU), then print how far the ship got and exit
+    #[structopt(long, conflicts_with = "capture")]
+    pub headless: bool,
+
+    /// How many frames a `--headless` or `--capture` run plays. Not for replays, which play until
+    /// the recording ends
+    #[structopt(long, parse(try_from_str = parse_frames))]
+    pub frames: Option<u64>,
+
+    /// Directory to load assets from, instead of `assets/` next to the game
+    #[structopt(long, parse(from_os_str))]
+    pub assets_dir: Option<PathBuf>,
+
+    /// Directory to load the config files from, instead of `config/` next to the game
+    #[structopt(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Most verbose log messages to show: off, error, warn, info, debug or trace. Overrides
+    /// `config/logger.ron`
+    #[structopt(long)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Log level for a single module, like `amethyst_assets=warn`. Can be given more than once
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_module_level))]
+    pub log_module: Vec<(String, LevelFilter)>,
+
+    /// Also write the log to this file, relative to the game's directory
+    #[structopt(long, parse(from_os_str))]
+    pub log_file: Option<PathBuf>,
+
+    /// Only show warnings and errors from Amethyst, while still showing all the game's own logs
+    #[structopt(long)]
+    pub quiet_engine: bool,
+
+    /// Open the window fullscreen, on whichever monitor it would have opened on
+    #[structopt(long)]
+    pub fullscreen: bool,
+
+    /// How to draw the scene: flat, shaded or pbr. Overrides `config/render.ron`
+    #[structopt(long)]
+    pub render: Option<RenderPipeline>,
+
+    /// Let the autopilot steer instead of the player
+    #[structopt(long, conflicts_with = "replay")]
+    pub autopilot: bool,
+
+    /// Play the whole `--headless` run at this difficulty stage (one of the stages in
+    /// `assets/config/gameplay.ron`, like `Fast`), instead of ramping up through them
+    #[structopt(long, requires = "headless")]
+    pub difficulty: Option<String>,
+
+    /// Play back a recorded run, like `replays/last_run.ron`. With `--headless`, exits with 1 if
+    /// it doesn't play out exactly as it was recorded
+    #[structopt(long, parse(from_os_str), conflicts_with = "capture")]
+    pub replay: Option<PathBuf>,
+
+    /// Play a run with the autopilot, drawing it offscreen, and save the frame after `--frames`
+    /// frames to this PNG, then exit. Needs a Vulkan driver, but a software one will do
+    #[structopt(long, parse(from_os_str))]
+    pub capture: Option<PathBuf>,
+}
+
+impl Args {
+    /// Parses the command line. If it's invalid (or `--help` was given), this prints a message
+    /// and exits the process.
+    pub fn parse() -> Self {
+        let args = Args::from_args();
+        if let Err(message) = args.validate() {
+            Error::with_description(&message, ErrorKind::ValueValidation).exit();
+        }
+        args
+    }
+
+    /// Checks what `structopt` can't check on its own.
+    fn validate(&self) -> Result<(), String> {
+        let directories = [
+            ("--assets-dir", &self.assets_dir),
+            ("--config", &self.config),
+        ];
+        for (flag, directory) in directories.iter() {
+            if let Some(directory) = directory {
+                if !directory.is_dir() {
+                    return Err(format!("{} {:?} isn't a directory", flag, directory));
+                }
+            }
+        }
+
+        if self.frames.is_some() {
+            if !self.headless && self.capture.is_none() {
+                return Err("--frames only applies to --headless and --capture runs".to_string());
+            }
+            if self.replay.is_some() {
+                return Err(
+                    "--frames can't be used with --replay, which plays until the recording ends"
+                        .to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_frames(frames: &str) -> Result<u64, String> {
+    match frames.parse() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(frames) => Ok(frames),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+fn parse_module_level(module_level: &str) -> Result<(String, LevelFilter), String> {
+    let mut parts = module_level.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(module), Some(level)) if !module.is_empty() => {
+            let level = level.parse().map_err(|_| {
+                format!(
+                    "`{}` isn't a log level (expected off, error, warn, info, debug or trace)",
+                    level
+                )
+            })?;
+            Ok((module.to_string(), level))
+        }
+        _ => Err("expected `MODULE=LEVEL`".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(args: &[&str]) -> Result<(), String> {
+        let args = Args::from_iter_safe(Some("cubefield").into_iter().chain(args.iter().cloned()))
+            .map_err(|error| error.message)?;
+        args.validate()
+    }
+
+    #[test]
+    fn frames_needs_a_headless_or_capture_run() {
+        assert!(validate(&["--headless", "--frames", "10"]).is_ok());
+        assert!(validate(&["--capture", "out.png", "--frames", "10"]).is_ok());
+        assert!(validate(&["--frames", "10"]).is_err());
+    }
+
+    #[test]
+    fn replays_take_neither_frames_nor_a_seed() {
+        assert!(validate(&["--headless", "--replay", "run.ron"]).is_ok());
+        assert!(validate(&["--headless", "--replay", "run.ron", "--frames", "10"]).is_err());
+        assert!(validate(&["--replay", "run.ron", "--seed", "3"]).is_err());
+    }
+}
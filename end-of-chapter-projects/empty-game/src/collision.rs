@@ -0,0 +1,147 @@
+use amethyst::core::math::Vector3;
+use amethyst::core::Transform;
+use amethyst::ecs::{Component, DenseVecStorage, Join, ReadStorage, System, Write};
+
+use crate::cube_field::Cube;
+use crate::ship::Ship;
+
+/// An axis-aligned bounding box, centred on the entity's `Transform`.
+pub struct Collider {
+    /// How far the box reaches from its centre along each axis (i.e. half its size).
+    pub half_extents: Vector3<f32>,
+}
+
+impl Collider {
+    pub fn new(half_x: f32, half_y: f32, half_z: f32) -> Self {
+        Collider {
+            half_extents: Vector3::new(half_x, half_y, half_z),
+        }
+    }
+}
+
+impl Component for Collider {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Set by `CollisionSystem` as soon as the ship hits a cube.
+#[derive(Default)]
+pub struct ShipCrashed(pub bool);
+
+/// Whether two axis-aligned bounding boxes overlap.
+///
+/// Each box is centred on its transform's translation and reaches `half_extents` out from there
+/// along each axis. The transforms' rotation and scale are ignored; a banking ship is still
+/// treated as level. Boxes that only touch along an edge don't count as overlapping.
+pub fn aabb_overlap(
+    a: &Transform,
+    a_half_extents: &Vector3<f32>,
+    b: &Transform,
+    b_half_extents: &Vector3<f32>,
+) -> bool {
+    let distance = a.translation() - b.translation();
+    let reach = a_half_extents + b_half_extents;
+
+    distance.x.abs() < reach.x && distance.y.abs() < reach.y && distance.z.abs() < reach.z
+}
+
+/// Checks the ship against every cube, and sets `ShipCrashed` if it hit any of them.
+pub struct CollisionSystem;
+
+impl<'s> System<'s> for CollisionSystem {
+    type SystemData = (
+        ReadStorage<'s, Ship>,
+        ReadStorage<'s, Cube>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Transform>,
+        Write<'s, ShipCrashed>,
+    );
+
+    fn run(&mut self, (ships, cubes, colliders, transforms, mut crashed): Self::SystemData) {
+        for (_, ship_collider, ship_transform) in (&ships, &colliders, &transforms).join() {
+            for (_, cube_collider, cube_transform) in (&cubes, &colliders, &transforms).join() {
+                if aabb_overlap(
+                    ship_transform,
+                    &ship_collider.half_extents,
+                    cube_transform,
+                    &cube_collider.half_extents,
+                ) {
+                    crashed.0 = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32, y: f32, z: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(x, y, z);
+        transform
+    }
+
+    fn unit() -> Vector3<f32> {
+        Vector3::new(0.5, 0.5, 0.5)
+    }
+
+    #[test]
+    fn overlapping_boxes_overlap() {
+        assert!(aabb_overlap(
+            &at(0.0, 0.0, 0.0),
+            &unit(),
+            &at(0.5, 0.5, 0.5),
+            &unit()
+        ));
+        assert!(aabb_overlap(
+            &at(0.0, 0.0, 0.0),
+            &unit(),
+            &at(0.0, 0.0, 0.0),
+            &unit()
+        ));
+    }
+
+    #[test]
+    fn touching_boxes_dont_overlap() {
+        let origin = at(0.0, 0.0, 0.0);
+        assert!(!aabb_overlap(&origin, &unit(), &at(1.0, 0.0, 0.0), &unit()));
+        assert!(!aabb_overlap(
+            &origin,
+            &unit(),
+            &at(0.0, -1.0, 0.0),
+            &unit()
+        ));
+        assert!(!aabb_overlap(&origin, &unit(), &at(0.0, 0.0, 1.0), &unit()));
+    }
+
+    #[test]
+    fn boxes_apart_on_any_one_axis_dont_overlap() {
+        let origin = at(0.0, 0.0, 0.0);
+        assert!(!aabb_overlap(&origin, &unit(), &at(1.5, 0.0, 0.0), &unit()));
+        assert!(!aabb_overlap(&origin, &unit(), &at(0.0, 1.5, 0.0), &unit()));
+        assert!(!aabb_overlap(
+            &origin,
+            &unit(),
+            &at(0.0, 0.0, -1.5),
+            &unit()
+        ));
+    }
+
+    #[test]
+    fn half_extents_of_both_boxes_count() {
+        let wide = Vector3::new(2.0, 0.5, 0.5);
+        assert!(aabb_overlap(
+            &at(0.0, 0.0, 0.0),
+            &wide,
+            &at(2.4, 0.0, 0.0),
+            &unit()
+        ));
+        assert!(!aabb_overlap(
+            &at(0.0, 0.0, 0.0),
+            &wide,
+            &at(2.6, 0.0, 0.0),
+            &unit()
+        ));
+    }
+}
@@ -0,0 +1,401 @@
+use std::path::{Path, PathBuf};
+
+use amethyst::config::Config;
+use amethyst::input::{
+    Axis, BindingError, Bindings, Button, InputEvent, InputHandler, StringBindings, VirtualKeyCode,
+};
+use amethyst::StateEvent;
+use log::warn;
+
+/// Keyboard steering axis.
+pub const STEER: &str = "steer";
+/// Gamepad steering axis. Bindings can only have one input per axis, so the gamepad stick gets
+/// its own, and `steering` combines the two. Gamepads are only read in builds with the `gamepad`
+/// feature; without it, the gamepad bindings are loaded but never pressed.
+pub const STEER_GAMEPAD: &str = "steer_gamepad";
+
+pub const PAUSE: &str = "pause";
+pub const CONFIRM: &str = "confirm";
+pub const BACK: &str = "back";
+pub const RESTART: &str = "restart";
+pub const MENU_UP: &str = "menu_up";
+pub const MENU_DOWN: &str = "menu_down";
+
+/// How far a gamepad stick has to be pushed before `is_any_input` counts it.
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Where the controls menu saves the player's bindings, in the user settings directory (never
+/// over the defaults in `config/bindings.ron`).
+pub struct BindingsPath(pub PathBuf);
+
+/// Loads the default bindings from `default_path`, then the player's own from `user_path` (if
+/// there are any) on top of them.
+///
+/// Every axis and action in the player's file replaces the default one of the same name, and
+/// anything it leaves out keeps its default, so controls added to the defaults later still get
+/// bound. Player bindings that clash with the defaults are skipped with a warning.
+pub fn load_bindings(
+    default_path: &Path,
+    user_path: Option<&Path>,
+) -> amethyst::Result<Bindings<StringBindings>> {
+    let mut bindings = Bindings::<StringBindings>::load_no_fallback(default_path)?;
+    bindings.check_invariants()?;
+
+    let user_path = match user_path {
+        Some(path) if path.exists() => path,
+        _ => return Ok(bindings),
+    };
+    match Bindings::<StringBindings>::load_no_fallback(user_path) {
+        Ok(overrides) => layer_bindings(&mut bindings, &overrides),
+        Err(error) => warn!(
+            "Couldn't load the player's bindings from {:?}, using the defaults: {}",
+            user_path, error
+        ),
+    }
+    Ok(bindings)
+}
+
+/// Replaces every axis and action in `bindings` that `overrides` has too.
+fn layer_bindings(bindings: &mut Bindings<StringBindings>, overrides: &Bindings<StringBindings>) {
+    for id in overrides.axes() {
+        let axis = match overrides.axis(id) {
+            Some(axis) => axis.clone(),
+            None => continue,
+        };
+        let old_axis = bindings.remove_axis(id);
+        if let Err(error) = bindings.insert_axis(id.clone(), axis) {
+            warn!("Couldn't use the player's binding for {}: {}", id, error);
+            if let Some(old_axis) = old_axis {
+                let _ = bindings.insert_axis(id.clone(), old_axis);
+            }
+        }
+    }
+
+    for id in overrides.actions() {
+        let combos = action_combos(overrides, id);
+        if let Err(error) = set_action_bindings(bindings, id, combos) {
+            warn!("Couldn't use the player's binding for {}: {}", id, error);
+        }
+    }
+}
+
+/// How hard the player is steering, from `-1.0` (full left) to `1.0` (full right), from
+/// whichever of the keyboard and gamepad is being pushed further.
+pub fn steering(input: &InputHandler<StringBindings>) -> f32 {
+    let keyboard = input.axis_value(STEER).unwrap_or(0.0);
+    let gamepad = input.axis_value(STEER_GAMEPAD).unwrap_or(0.0);
+
+    let steer = if gamepad.abs() > keyboard.abs() {
+        gamepad
+    } else {
+        keyboard
+    };
+    steer.max(-1.0).min(1.0)
+}
+
+/// The name of the action that was just pressed, if this event is an action press.
+pub fn action_pressed(event: &StateEvent) -> Option<&str> {
+    match event {
+        StateEvent::Input(InputEvent::ActionPressed(action)) => Some(action),
+        _ => None,
+    }
+}
+
+/// Whether this event is the player doing anything at all: pressing any key or button, moving
+/// the mouse, or pushing a gamepad stick (far enough that a stick that doesn't quite centre
+/// doesn't count).
+pub fn is_any_input(event: &StateEvent) -> bool {
+    match event {
+        StateEvent::Input(InputEvent::ButtonPressed(_))
+        | StateEvent::Input(InputEvent::MouseMoved { .. })
+        | StateEvent::Input(InputEvent::MouseWheelMoved(_)) => true,
+        StateEvent::Input(InputEvent::ControllerAxisMoved { value, .. }) => {
+            value.abs() > STICK_THRESHOLD
+        }
+        _ => false,
+    }
+}
+
+/// The controls that can be rebound from the controls menu.
+///
+/// Only keyboard bindings are rebindable; gamepad bindings are left exactly as they are in the
+/// bindings file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    SteerLeft,
+    SteerRight,
+    Pause,
+    Confirm,
+    Back,
+    Restart,
+}
+
+impl Control {
+    pub const ALL: [Control; 6] = [
+        Control::SteerLeft,
+        Control::SteerRight,
+        Control::Pause,
+        Control::Confirm,
+        Control::Back,
+        Control::Restart,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Control::SteerLeft => "Steer Left",
+            Control::SteerRight => "Steer Right",
+            Control::Pause => "Pause",
+            Control::Confirm => "Confirm",
+            Control::Back => "Back",
+            Control::Restart => "Restart",
+        }
+    }
+
+    fn action(self) -> Option<&'static str> {
+        match self {
+            Control::SteerLeft | Control::SteerRight => None,
+            Control::Pause => Some(PAUSE),
+            Control::Confirm => Some(CONFIRM),
+            Control::Back => Some(BACK),
+            Control::Restart => Some(RESTART),
+        }
+    }
+
+    /// The keyboard key this control is currently bound to, if any.
+    pub fn key(self, bindings: &Bindings<StringBindings>) -> Option<VirtualKeyCode> {
+        match self.action() {
+            Some(action) => bindings.action_bindings(action).find_map(single_key),
+            None => match bindings.axis(STEER) {
+                Some(Axis::Emulated { pos, neg }) => {
+                    let button = if self == Control::SteerLeft { neg } else { pos };
+                    match button {
+                        Button::Key(key) => Some(*key),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Binds this control to `key`, replacing whichever keyboard key it was bound to before.
+    ///
+    /// If `key` can't be bound (because it's already used by an axis, for example), the old
+    /// binding is kept.
+    pub fn rebind(
+        self,
+        bindings: &mut Bindings<StringBindings>,
+        key: VirtualKeyCode,
+    ) -> Result<(), BindingError<StringBindings>> {
+        match self.action() {
+            Some(action) => rebind_action(bindings, action, key),
+            None => self.rebind_steering(bindings, key),
+        }
+    }
+
+    fn rebind_steering(
+        self,
+        bindings: &mut Bindings<StringBindings>,
+        key: VirtualKeyCode,
+    ) -> Result<(), BindingError<StringBindings>> {
+        let old_axis = bindings.remove_axis(STEER);
+        let (pos, neg) = match &old_axis {
+            Some(Axis::Emulated { pos, neg }) => (pos.clone(), neg.clone()),
+            _ => (
+                Button::Key(VirtualKeyCode::Right),
+                Button::Key(VirtualKeyCode::Left),
+            ),
+        };
+
+        let new_axis = if self == Control::SteerLeft {
+            Axis::Emulated {
+                pos,
+                neg: Button::Key(key),
+            }
+        } else {
+            Axis::Emulated {
+                pos: Button::Key(key),
+                neg,
+            }
+        };
+
+        bindings
+            .insert_axis(STEER.to_string(), new_axis)
+            .map(|_| ())
+            .map_err(|error| {
+                if let Some(old_axis) = old_axis {
+                    let _ = bindings.insert_axis(STEER.to_string(), old_axis);
+                }
+                error
+            })
+    }
+}
+
+/// Binds `action` to `key` in place of the key it shows in the menu (its first single-key
+/// binding), leaving its other keys and its gamepad buttons alone.
+fn rebind_action(
+    bindings: &mut Bindings<StringBindings>,
+    action: &str,
+    key: VirtualKeyCode,
+) -> Result<(), BindingError<StringBindings>> {
+    let new_combo = vec![Button::Key(key)];
+    let mut combos = action_combos(bindings, action);
+    match combos.iter().position(|combo| single_key(combo).is_some()) {
+        Some(index) => combos[index] = new_combo,
+        None => combos.insert(0, new_combo),
+    }
+
+    // If the action was already bound to `key` as well, that binding is now a duplicate
+    let mut seen = Vec::new();
+    combos.retain(|combo| {
+        let first = !seen.contains(combo);
+        seen.push(combo.clone());
+        first
+    });
+
+    set_action_bindings(bindings, action, combos)
+}
+
+/// Every binding of `action`, in order.
+fn action_combos(bindings: &Bindings<StringBindings>, action: &str) -> Vec<Vec<Button>> {
+    bindings
+        .action_bindings(action)
+        .map(|combo| combo.to_vec())
+        .collect()
+}
+
+/// Replaces all of `action`'s bindings with `combos`, keeping their order. If any of them can't
+/// be bound, the old bindings are put back.
+fn set_action_bindings(
+    bindings: &mut Bindings<StringBindings>,
+    action: &str,
+    combos: Vec<Vec<Button>>,
+) -> Result<(), BindingError<StringBindings>> {
+    let old_combos = action_combos(bindings, action);
+    clear_action(bindings, action);
+
+    for combo in combos {
+        if let Err(error) = bindings.insert_action_binding(action.to_string(), combo) {
+            clear_action(bindings, action);
+            for combo in old_combos {
+                let _ = bindings.insert_action_binding(action.to_string(), combo);
+            }
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+fn clear_action(bindings: &mut Bindings<StringBindings>, action: &str) {
+    for combo in action_combos(bindings, action) {
+        let _ = bindings.remove_action_binding(action, &combo);
+    }
+}
+
+/// The key in a button combination, if the combination is just a single key.
+fn single_key(combo: &[Button]) -> Option<VirtualKeyCode> {
+    match combo {
+        [Button::Key(key)] => Some(*key),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::input::ControllerButton;
+
+    const DEFAULTS: &str = r#"(
+        axes: {
+            "steer": Emulated(pos: Key(Right), neg: Key(Left)),
+        },
+        actions: {
+            "pause": [[Key(P)], [Controller(0, Start)]],
+            "confirm": [[Key(Return)], [Key(Space)], [Controller(0, A)]],
+        },
+    )"#;
+
+    fn parse(ron: &str) -> Bindings<StringBindings> {
+        ron::de::from_str(ron).expect("Test bindings don't parse")
+    }
+
+    fn confirm(bindings: &Bindings<StringBindings>) -> Vec<Vec<Button>> {
+        action_combos(bindings, CONFIRM)
+    }
+
+    #[test]
+    fn rebinding_replaces_only_the_key_shown() {
+        let mut bindings = parse(DEFAULTS);
+        Control::Confirm
+            .rebind(&mut bindings, VirtualKeyCode::X)
+            .unwrap();
+
+        assert_eq!(Control::Confirm.key(&bindings), Some(VirtualKeyCode::X));
+        assert_eq!(
+            confirm(&bindings),
+            vec![
+                vec![Button::Key(VirtualKeyCode::X)],
+                vec![Button::Key(VirtualKeyCode::Space)],
+                vec![Button::Controller(0, ControllerButton::A)],
+            ]
+        );
+    }
+
+    #[test]
+    fn rebinding_to_another_key_of_the_same_action_drops_the_duplicate() {
+        let mut bindings = parse(DEFAULTS);
+        Control::Confirm
+            .rebind(&mut bindings, VirtualKeyCode::Space)
+            .unwrap();
+
+        assert_eq!(
+            confirm(&bindings),
+            vec![
+                vec![Button::Key(VirtualKeyCode::Space)],
+                vec![Button::Controller(0, ControllerButton::A)],
+            ]
+        );
+    }
+
+    #[test]
+    fn rebinding_a_steering_key_keeps_the_other_one() {
+        let mut bindings = parse(DEFAULTS);
+        Control::SteerLeft
+            .rebind(&mut bindings, VirtualKeyCode::A)
+            .unwrap();
+
+        assert_eq!(Control::SteerLeft.key(&bindings), Some(VirtualKeyCode::A));
+        assert_eq!(
+            Control::SteerRight.key(&bindings),
+            Some(VirtualKeyCode::Right)
+        );
+    }
+
+    #[test]
+    fn failed_rebind_keeps_the_old_bindings() {
+        let mut bindings = parse(DEFAULTS);
+        // Right is already part of the steering axis
+        assert!(Control::Pause
+            .rebind(&mut bindings, VirtualKeyCode::Right)
+            .is_err());
+        assert_eq!(Control::Pause.key(&bindings), Some(VirtualKeyCode::P));
+        assert_eq!(action_combos(&bindings, PAUSE).len(), 2);
+    }
+
+    #[test]
+    fn player_bindings_replace_only_what_they_set() {
+        let mut bindings = parse(DEFAULTS);
+        let overrides = parse(r#"(axes: {}, actions: { "pause": [[Key(O)]] })"#);
+        layer_bindings(&mut bindings, &overrides);
+
+        assert_eq!(
+            action_combos(&bindings, PAUSE),
+            vec![vec![Button::Key(VirtualKeyCode::O)]]
+        );
+        assert_eq!(confirm(&bindings).len(), 3);
+        assert_eq!(
+            Control::SteerLeft.key(&bindings),
+            Some(VirtualKeyCode::Left)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use amethyst::input::{is_close_requested, InputHandler, StringBindings, VirtualKeyCode};
+use amethyst::winit::{ElementState, Event, KeyboardInput, WindowEvent};
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans};
+use log::warn;
+
+use crate::controls::{BindingsPath, Control};
+use crate::menu::{Menu, MenuAction};
+use crate::settings;
+
+/// Action events can still be on their way for a couple of frames after a key is rebound (the
+/// input system reads the key press with the *new* bindings), so the menu ignores them for this
+/// many frames afterwards.
+const REBIND_COOLDOWN_FRAMES: u8 = 2;
+
+/// Lets the player rebind the keyboard controls, and saves the result to their own bindings file
+/// in the user settings directory.
+///
+/// Picking a control waits for the next key press and binds the control to that key.
+#[derive(Default)]
+pub struct ControlsState {
+    menu: Option<Menu>,
+    /// The control waiting for a key press, if any.
+    rebinding: Option<Control>,
+    cooldown_frames: u8,
+}
+
+impl SimpleState for ControlsState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        let world = state_data.world;
+
+        let mut labels: Vec<String> = {
+            let input = world.read_resource::<InputHandler<StringBindings>>();
+            Control::ALL
+                .iter()
+                .map(|control| control_label(*control, &input))
+                .collect()
+        };
+        labels.push("Back".to_string());
+
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.menu = Some(Menu::create(world, "Controls", &labels));
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(menu) = self.menu.take() {
+            menu.delete(state_data.world);
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        if let Some(control) = self.rebinding {
+            if let Some(key) = key_pressed(&event) {
+                self.rebinding = None;
+                self.cooldown_frames = REBIND_COOLDOWN_FRAMES;
+                self.rebind(state_data, control, key);
+            }
+            return Trans::None;
+        }
+
+        if self.cooldown_frames > 0 {
+            return Trans::None;
+        }
+
+        let menu = match self.menu.as_mut() {
+            Some(menu) => menu,
+            None => return Trans::None,
+        };
+        match menu.handle_event(state_data.world, &event) {
+            Some(MenuAction::Confirm(index)) if index < Control::ALL.len() => {
+                let control = Control::ALL[index];
+                menu.set_label(
+                    state_data.world,
+                    index,
+                    &format!("{}: press a key...", control.label()),
+                );
+                self.rebinding = Some(control);
+                Trans::None
+            }
+            Some(MenuAction::Confirm(_)) | Some(MenuAction::Back) => Trans::Pop,
+            None => Trans::None,
+        }
+    }
+
+    fn update(&mut self, _state_data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.cooldown_frames = self.cooldown_frames.saturating_sub(1);
+        Trans::None
+    }
+}
+
+impl ControlsState {
+    fn rebind(
+        &mut self,
+        state_data: StateData<'_, GameData<'_, '_>>,
+        control: Control,
+        key: VirtualKeyCode,
+    ) {
+        let world = state_data.world;
+        let mut input = world.write_resource::<InputHandler<StringBindings>>();
+
+        if let Err(error) = control.rebind(&mut input.bindings, key) {
+            warn!("Couldn't bind {} to {:?}: {}", control.label(), key, error);
+        } else {
+            let path = &world.read_resource::<BindingsPath>().0;
+            if let Err(error) = settings::write(&input.bindings, path) {
+                warn!("Couldn't save the input bindings to {:?}: {}", path, error);
+            }
+        }
+
+        if let Some(menu) = &self.menu {
+            let index = Control::ALL
+                .iter()
+                .position(|other| *other == control)
+                .expect("Rebound a control that isn't in the menu");
+            menu.set_label(world, index, &control_label(control, &input));
+        }
+    }
+}
+
+/// "Steer Left: Left", "Pause: P", etc.
+fn control_label(control: Control, input: &InputHandler<StringBindings>) -> String {
+    match control.key(&input.bindings) {
+        Some(key) => format!("{}: {:?}", control.label(), key),
+        None => format!("{}: (unbound)", control.label()),
+    }
+}
+
+/// The key that was just pressed, if this event is a key press.
+fn key_pressed(event: &StateEvent) -> Option<VirtualKeyCode> {
+    match event {
+        StateEvent::Window(Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        }) => Some(*key),
+        _ => None,
+    }
+}
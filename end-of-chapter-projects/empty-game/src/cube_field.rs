@@ -0,0 +1,238 @@
+use amethyst::assets::{AssetLoaderSystemData, Handle};
+use amethyst::core::Transform;
+use amethyst::ecs::{
+    Component, Entities, Join, NullStorage, Read, System, World, Write, WriteExpect, WriteStorage,
+};
+use amethyst::renderer::{
+    rendy::mesh::{Normal, Position, Tangent, TexCoord},
+    shape::Shape,
+    Material, MaterialDefaults, Mesh,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::collision::Collider;
+use crate::difficulty::Difficulty;
+use crate::gameplay_config::{clamp_at_least, GameplayConfig};
+use crate::rng::GameRng;
+use crate::simulation::{Interpolation, SimulationTime};
+
+/// Marks an entity as one of the cubes in the cube field.
+#[derive(Default)]
+pub struct Cube;
+
+impl Component for Cube {
+    type Storage = NullStorage<Self>;
+}
+
+/// Tuning for the cube field, loaded as part of `GameplayConfig`.
+///
+/// The ship stays near the origin and the field scrolls towards it (in the positive Z
+/// direction), so "ahead of the ship" is negative Z and "behind the camera" is positive Z.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CubeFieldConfig {
+    /// How many cubes to spawn per unit of distance travelled.
+    pub density: f32,
+    /// Cubes spawn anywhere between `-spread` and `spread` on the X axis.
+    pub spread: f32,
+    /// How far ahead of the ship new cubes are spawned.
+    pub spawn_distance: f32,
+    /// Cubes are despawned once they scroll past this Z coordinate (just behind the camera).
+    pub despawn_z: f32,
+    /// How fast the field scrolls towards the ship, in units per second.
+    pub speed: f32,
+    /// Length of each side of a cube. This is read at the start of each run (the cubes' mesh is
+    /// built then), so changing it only affects the next run.
+    pub cube_size: f32,
+}
+
+impl Default for CubeFieldConfig {
+    fn default() -> Self {
+        CubeFieldConfig {
+            density: 0.6,
+            spread: 30.0,
+            spawn_distance: 100.0,
+            despawn_z: 12.0,
+            speed: 20.0,
+            cube_size: 1.0,
+        }
+    }
+}
+
+impl CubeFieldConfig {
+    /// Clamps the values the field can't be built from (a negative spread, say).
+    pub fn validate(&mut self) {
+        clamp_at_least("cube_field.density", &mut self.density, 0.0);
+        clamp_at_least("cube_field.spread", &mut self.spread, 0.0);
+        clamp_at_least("cube_field.speed", &mut self.speed, 0.0);
+        clamp_at_least("cube_field.cube_size", &mut self.cube_size, 0.01);
+    }
+}
+
+/// Bookkeeping for `CubeFieldSystem`. Reset at the start of every run.
+pub struct CubeField {
+    distance_since_spawn: f32,
+    cube_size: f32,
+}
+
+impl CubeField {
+    /// A fresh field for a run with `config`.
+    pub fn new(config: &CubeFieldConfig) -> Self {
+        CubeField {
+            distance_since_spawn: 0.0,
+            cube_size: config.cube_size,
+        }
+    }
+
+    /// Length of each side of this run's cubes.
+    pub fn cube_size(&self) -> f32 {
+        self.cube_size
+    }
+}
+
+impl Default for CubeField {
+    fn default() -> Self {
+        CubeField::new(&CubeFieldConfig::default())
+    }
+}
+
+/// The mesh and material every cube is rendered with.
+///
+/// This resource only exists when the game is rendering; headless runs still spawn cubes, they
+/// just don't get anything to draw them with.
+pub struct CubeAssets {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<Material>,
+}
+
+/// Loads the cube mesh and material for the current run's `CubeField`, and stores them in a
+/// `CubeAssets` resource.
+pub fn initialize_cube_assets(world: &mut World) {
+    let half_size = world.read_resource::<CubeField>().cube_size() / 2.0;
+
+    let mesh = world.exec(|loader: AssetLoaderSystemData<'_, Mesh>| {
+        loader.load_from_data(
+            Shape::Cube
+                .generate::<(Vec<Position>, Vec<Normal>, Vec<Tangent>, Vec<TexCoord>)>(Some((
+                    half_size, half_size, half_size,
+                )))
+                .into(),
+            (),
+        )
+    });
+
+    let material_defaults = world.read_resource::<MaterialDefaults>().0.clone();
+    let material = world.exec(|loader: AssetLoaderSystemData<'_, Material>| {
+        loader.load_from_data(
+            Material {
+                ..material_defaults
+            },
+            (),
+        )
+    });
+
+    world.add_resource(CubeAssets { mesh, material });
+}
+
+/// Scrolls the cube field towards the ship, spawns new cubes ahead of it, and despawns the ones
+/// that have scrolled behind the camera.
+pub struct CubeFieldSystem;
+
+impl<'s> System<'s> for CubeFieldSystem {
+    type SystemData = (
+        Entities<'s>,
+        Write<'s, CubeField>,
+        Read<'s, GameplayConfig>,
+        Read<'s, Difficulty>,
+        Read<'s, SimulationTime>,
+        WriteExpect<'s, GameRng>,
+        Option<Read<'s, CubeAssets>>,
+        WriteStorage<'s, Cube>,
+        WriteStorage<'s, Collider>,
+        WriteStorage<'s, Interpolation>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Handle<Mesh>>,
+        WriteStorage<'s, Handle<Material>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut field,
+            config,
+            difficulty,
+            time,
+            mut rng,
+            assets,
+            mut cubes,
+            mut colliders,
+            mut interpolations,
+            mut transforms,
+            mut meshes,
+            mut materials,
+        ): Self::SystemData,
+    ) {
+        let config = &config.cube_field;
+        let distance = difficulty.speed(config) * time.tick_seconds();
+
+        // Scroll every cube towards the ship, and get rid of the ones we've passed
+        for (entity, _, transform) in (&entities, &cubes, &mut transforms).join() {
+            transform.prepend_translation_z(distance);
+            if transform.translation().z > config.despawn_z {
+                entities
+                    .delete(entity)
+                    .expect("Tried to despawn a cube that was already gone");
+            }
+        }
+
+        let density = difficulty.density(config);
+        if density <= 0.0 {
+            return;
+        }
+
+        // Spawn a new cube every `1 / density` units of distance. Any leftover distance pushes
+        // the new cube a little closer, so the spacing stays even at any tick rate.
+        let spacing = 1.0 / density;
+        // `gen_range` panics on an empty range, so a field with no spread spawns dead ahead
+        let spread = difficulty.spread(config);
+        let half_size = field.cube_size / 2.0;
+        field.distance_since_spawn += distance;
+        while field.distance_since_spawn >= spacing {
+            field.distance_since_spawn -= spacing;
+
+            let mut transform = Transform::default();
+            let x = if spread > 0.0 {
+                rng.gen_range(-spread, spread)
+            } else {
+                0.0
+            };
+            transform.set_translation_xyz(
+                x,
+                0.0,
+                -config.spawn_distance + field.distance_since_spawn,
+            );
+
+            let cube = entities
+                .build_entity()
+                .with(Cube, &mut cubes)
+                .with(
+                    Collider::new(half_size, half_size, half_size),
+                    &mut colliders,
+                )
+                .with(Interpolation::new(&transform), &mut interpolations)
+                .with(transform, &mut transforms)
+                .build();
+
+            if let Some(assets) = &assets {
+                meshes
+                    .insert(cube, assets.mesh.clone())
+                    .expect("Failed to add a mesh to a new cube");
+                materials
+                    .insert(cube, assets.material.clone())
+                    .expect("Failed to add a material to a new cube");
+            }
+        }
+    }
+}
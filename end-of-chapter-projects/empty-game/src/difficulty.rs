@@ -0,0 +1,343 @@
+use amethyst::ecs::{Read, System, Write};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::cube_field::CubeFieldConfig;
+use crate::gameplay_config::{clamp_at_least, GameplayConfig};
+use crate::simulation::SimulationTime;
+
+/// One point on the difficulty curve, loaded as part of `GameplayConfig`.
+///
+/// The multipliers scale the matching `CubeFieldConfig` values. Between two stages they're
+/// interpolated linearly over time, so the game ramps up smoothly instead of jumping.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DifficultyStage {
+    pub name: String,
+    /// Seconds into the run that this stage starts at.
+    pub start_time: f32,
+    /// Multiplies how fast the cube field scrolls.
+    pub speed: f32,
+    /// Multiplies how many cubes spawn per unit of distance.
+    pub density: f32,
+    /// Multiplies how far apart cubes are spread sideways.
+    pub spread: f32,
+}
+
+impl DifficultyStage {
+    fn new(name: &str, start_time: f32, speed: f32, density: f32, spread: f32) -> Self {
+        DifficultyStage {
+            name: name.to_string(),
+            start_time,
+            speed,
+            density,
+            spread,
+        }
+    }
+}
+
+/// The difficulty curve: a list of stages, in order of `start_time`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DifficultyConfig {
+    pub stages: Vec<DifficultyStage>,
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        DifficultyConfig {
+            stages: vec![
+                DifficultyStage::new("Warm-up", 0.0, 1.0, 1.0, 1.0),
+                DifficultyStage::new("Cruising", 20.0, 1.3, 1.3, 0.9),
+                DifficultyStage::new("Fast", 45.0, 1.7, 1.6, 0.8),
+                DifficultyStage::new("Insane", 90.0, 2.2, 2.0, 0.7),
+            ],
+        }
+    }
+}
+
+impl DifficultyConfig {
+    /// Clamps multipliers that would turn the cube field inside out, and puts the stages in order
+    /// of `start_time`, since `Difficulty::at` relies on it.
+    pub fn validate(&mut self) {
+        for stage in &mut self.stages {
+            let name = format!("difficulty stage `{}`", stage.name);
+            clamp_at_least(&format!("{} speed", name), &mut stage.speed, 0.0);
+            clamp_at_least(&format!("{} density", name), &mut stage.density, 0.0);
+            clamp_at_least(&format!("{} spread", name), &mut stage.spread, 0.0);
+        }
+
+        let in_order = self
+            .stages
+            .windows(2)
+            .all(|pair| pair[0].start_time <= pair[1].start_time);
+        if !in_order {
+            warn!(
+                "The gameplay config's difficulty stages aren't in order of start_time, so \
+                 they've been sorted"
+            );
+            // `sort_by` is stable, so stages starting at the same time stay in file order
+            self.stages.sort_by(|a, b| {
+                a.start_time
+                    .partial_cmp(&b.start_time)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+    }
+
+    /// Drops every stage except the one called `name` (ignoring case), and starts that one
+    /// right away, so whole runs are played at that difficulty instead of ramping up to it.
+    ///
+    /// Fails, listing the stages there are, if there isn't one called `name`.
+    pub fn hold_stage(&mut self, name: &str) -> Result<(), String> {
+        let stage = self
+            .stages
+            .iter()
+            .position(|stage| stage.name.eq_ignore_ascii_case(name));
+
+        match stage {
+            Some(stage) => {
+                let mut stage = self.stages.swap_remove(stage);
+                stage.start_time = 0.0;
+                self.stages = vec![stage];
+                Ok(())
+            }
+            None => {
+                let names: Vec<&str> = self
+                    .stages
+                    .iter()
+                    .map(|stage| stage.name.as_str())
+                    .collect();
+                Err(format!(
+                    "there's no difficulty stage called `{}` (expected one of: {})",
+                    name,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// How hard the current run is, based on how long it has been going.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    elapsed: f32,
+    stage: usize,
+    speed: f32,
+    density: f32,
+    spread: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty {
+            elapsed: 0.0,
+            stage: 0,
+            speed: 1.0,
+            density: 1.0,
+            spread: 1.0,
+        }
+    }
+}
+
+impl Difficulty {
+    /// The difficulty `elapsed` seconds into a run.
+    ///
+    /// Before the first stage (or with no stages at all) nothing is scaled, and after the last
+    /// stage its multipliers are held.
+    pub fn at(config: &DifficultyConfig, elapsed: f32) -> Self {
+        let stages = &config.stages;
+        let stage = match stages.iter().rposition(|stage| stage.start_time <= elapsed) {
+            Some(stage) => stage,
+            None => {
+                return Difficulty {
+                    elapsed,
+                    ..Default::default()
+                }
+            }
+        };
+
+        let current = &stages[stage];
+        let (speed, density, spread) = match stages.get(stage + 1) {
+            Some(next) if next.start_time > current.start_time => {
+                let t = (elapsed - current.start_time) / (next.start_time - current.start_time);
+                (
+                    lerp(current.speed, next.speed, t),
+                    lerp(current.density, next.density, t),
+                    lerp(current.spread, next.spread, t),
+                )
+            }
+            _ => (current.speed, current.density, current.spread),
+        };
+
+        Difficulty {
+            elapsed,
+            stage,
+            speed,
+            density,
+            spread,
+        }
+    }
+
+    /// Seconds since the run started.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Index of the current stage in `DifficultyConfig::stages`.
+    pub fn stage(&self) -> usize {
+        self.stage
+    }
+
+    /// How fast the cube field scrolls right now, in units per second.
+    pub fn speed(&self, field: &CubeFieldConfig) -> f32 {
+        field.speed * self.speed
+    }
+
+    /// How many cubes spawn per unit of distance right now.
+    pub fn density(&self, field: &CubeFieldConfig) -> f32 {
+        field.density * self.density
+    }
+
+    /// How far cubes spread out sideways right now.
+    pub fn spread(&self, field: &CubeFieldConfig) -> f32 {
+        field.spread * self.spread
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+/// Advances the `Difficulty` along the configured curve as the run goes on.
+pub struct DifficultySystem;
+
+impl<'s> System<'s> for DifficultySystem {
+    type SystemData = (
+        Write<'s, Difficulty>,
+        Read<'s, GameplayConfig>,
+        Read<'s, SimulationTime>,
+    );
+
+    fn run(&mut self, (mut difficulty, config, time): Self::SystemData) {
+        let elapsed = difficulty.elapsed + time.tick_seconds();
+        let next = Difficulty::at(&config.difficulty, elapsed);
+
+        if next.stage != difficulty.stage {
+            if let Some(stage) = config.difficulty.stages.get(next.stage) {
+                info!("Difficulty is now {} ({:.0}s in)", stage.name, elapsed);
+            }
+        }
+
+        *difficulty = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> DifficultyConfig {
+        DifficultyConfig {
+            stages: vec![
+                DifficultyStage::new("Easy", 0.0, 1.0, 1.0, 1.0),
+                DifficultyStage::new("Medium", 10.0, 2.0, 3.0, 0.5),
+                DifficultyStage::new("Hard", 20.0, 4.0, 5.0, 0.25),
+            ],
+        }
+    }
+
+    fn field() -> CubeFieldConfig {
+        CubeFieldConfig {
+            speed: 10.0,
+            density: 1.0,
+            spread: 20.0,
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn stages_start_at_their_start_time() {
+        let config = config();
+        assert_eq!(Difficulty::at(&config, 0.0).stage(), 0);
+        assert_eq!(Difficulty::at(&config, 9.99).stage(), 0);
+        assert_eq!(Difficulty::at(&config, 10.0).stage(), 1);
+        assert_eq!(Difficulty::at(&config, 20.0).stage(), 2);
+
+        let medium = Difficulty::at(&config, 10.0);
+        assert_close(medium.speed(&field()), 20.0);
+        assert_close(medium.density(&field()), 3.0);
+        assert_close(medium.spread(&field()), 10.0);
+    }
+
+    #[test]
+    fn multipliers_are_interpolated_between_stages() {
+        let difficulty = Difficulty::at(&config(), 15.0);
+        assert_eq!(difficulty.stage(), 1);
+        assert_close(difficulty.speed(&field()), 30.0);
+        assert_close(difficulty.density(&field()), 4.0);
+        assert_close(difficulty.spread(&field()), 7.5);
+    }
+
+    #[test]
+    fn last_stage_is_held() {
+        let difficulty = Difficulty::at(&config(), 1000.0);
+        assert_eq!(difficulty.stage(), 2);
+        assert_close(difficulty.elapsed(), 1000.0);
+        assert_close(difficulty.speed(&field()), 40.0);
+        assert_close(difficulty.density(&field()), 5.0);
+        assert_close(difficulty.spread(&field()), 5.0);
+    }
+
+    #[test]
+    fn nothing_is_scaled_without_stages() {
+        let difficulty = Difficulty::at(&DifficultyConfig { stages: Vec::new() }, 5.0);
+        assert_close(difficulty.speed(&field()), 10.0);
+        assert_close(difficulty.spread(&field()), 20.0);
+    }
+
+    #[test]
+    fn validate_sorts_the_stages_by_start_time() {
+        let mut config = config();
+        config.stages.swap(1, 2);
+        config.validate();
+
+        let names: Vec<&str> = config
+            .stages
+            .iter()
+            .map(|stage| stage.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Easy", "Medium", "Hard"]);
+        assert_eq!(Difficulty::at(&config, 15.0).stage(), 1);
+        assert_eq!(Difficulty::at(&config, 25.0).stage(), 2);
+    }
+
+    #[test]
+    fn hold_stage_keeps_only_that_stage_from_the_start() {
+        let mut config = config();
+        config.hold_stage("medium").unwrap();
+        assert_eq!(config.stages.len(), 1);
+        assert_eq!(config.stages[0].name, "Medium");
+
+        let difficulty = Difficulty::at(&config, 0.0);
+        assert_close(difficulty.speed(&field()), 20.0);
+        assert_close(Difficulty::at(&config, 100.0).speed(&field()), 20.0);
+    }
+
+    #[test]
+    fn hold_stage_fails_on_an_unknown_name() {
+        let mut config = config();
+        let error = config.hold_stage("Nightmare").unwrap_err();
+        assert!(error.contains("Nightmare"));
+        assert!(error.contains("Easy, Medium, Hard"));
+        assert_eq!(config.stages.len(), 3);
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use amethyst::config::Config;
+use amethyst::ecs::{ReadExpect, System};
+use amethyst::renderer::{
+    plugins::{RenderFlat3D, RenderPbr3D, RenderShaded3D, RenderSkybox, RenderToWindow},
+    types::DefaultBackend,
+    RenderingBundle,
+};
+use amethyst::ui::RenderUi;
+use amethyst::window::{DisplayConfig, Window};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::offscreen::RenderToImage;
+
+/// Loads the window settings from `path` (normally `config/display.ron`).
+///
+/// If the file is missing or broken, the game still starts, in a 1024x768 window.
+pub fn load_display_config(path: &Path) -> DisplayConfig {
+    DisplayConfig::load_no_fallback(path).unwrap_or_else(|error| {
+        warn!(
+            "Couldn't load the display config from {:?}, using the defaults: {}",
+            path, error
+        );
+        DisplayConfig {
+            title: "Cubefield".to_string(),
+            dimensions: Some((1024, 768)),
+            ..Default::default()
+        }
+    })
+}
+
+/// Which plugin draws the 3D scene.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RenderPipeline {
+    /// No lighting at all; the cheapest option, for weak machines.
+    Flat,
+    /// Simple (Phong) lighting.
+    Shaded,
+    /// Physically based rendering; the best looking, and the most expensive.
+    Pbr,
+}
+
+impl FromStr for RenderPipeline {
+    type Err = UnknownRenderPipeline;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "flat" => Ok(RenderPipeline::Flat),
+            "shaded" => Ok(RenderPipeline::Shaded),
+            "pbr" => Ok(RenderPipeline::Pbr),
+            _ => Err(UnknownRenderPipeline(name.to_string())),
+        }
+    }
+}
+
+/// Returned when parsing a `RenderPipeline` from a name that isn't one.
+#[derive(Debug)]
+pub struct UnknownRenderPipeline(String);
+
+impl fmt::Display for UnknownRenderPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown render pipeline `{}` (expected `flat`, `shaded` or `pbr`)",
+            self.0
+        )
+    }
+}
+
+impl Error for UnknownRenderPipeline {}
+
+/// Contents of `config/render.ron`: how the scene is drawn, as opposed to the window it's drawn
+/// in.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    /// What the renderer draws wherever there's nothing else, as `[Red, Green, Blue, Alpha]`.
+    pub clear_color: [f32; 4],
+    pub pipeline: RenderPipeline,
+    /// Draw a gradient sky behind the scene, instead of just the clear color.
+    pub skybox: bool,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfig {
+            clear_color: [0.95, 0.95, 0.95, 1.0],
+            pipeline: RenderPipeline::Shaded,
+            skybox: false,
+        }
+    }
+}
+
+/// Builds the `RenderingBundle` with whichever plugins `render_config` asks for.
+pub fn rendering_bundle(
+    display_config: DisplayConfig,
+    render_config: &RenderConfig,
+) -> RenderingBundle<DefaultBackend> {
+    // The RenderToWindow plugin provides all the scaffolding for opening a window and drawing on it
+    let bundle = RenderingBundle::<DefaultBackend>::new().with_plugin(
+        RenderToWindow::from_config(display_config).with_clear(render_config.clear_color),
+    );
+    with_scene_plugins(bundle, render_config)
+}
+
+/// Builds a `RenderingBundle` like `rendering_bundle`, but drawing into a `width` by `height`
+/// image instead of a window; for `--capture`.
+pub fn offscreen_rendering_bundle(
+    width: u32,
+    height: u32,
+    render_config: &RenderConfig,
+) -> RenderingBundle<DefaultBackend> {
+    let bundle = RenderingBundle::<DefaultBackend>::new()
+        .with_plugin(RenderToImage::new(width, height).with_clear(render_config.clear_color));
+    with_scene_plugins(bundle, render_config)
+}
+
+/// Adds the plugins that draw the scene and the menus, whatever they're drawn into.
+fn with_scene_plugins(
+    bundle: RenderingBundle<DefaultBackend>,
+    render_config: &RenderConfig,
+) -> RenderingBundle<DefaultBackend> {
+    let bundle = match render_config.pipeline {
+        RenderPipeline::Flat => bundle.with_plugin(RenderFlat3D::default()),
+        RenderPipeline::Shaded => bundle.with_plugin(RenderShaded3D::default()),
+        RenderPipeline::Pbr => bundle.with_plugin(RenderPbr3D::default()),
+    };
+
+    let bundle = if render_config.skybox {
+        bundle.with_plugin(RenderSkybox::default())
+    } else {
+        bundle
+    };
+
+    // The RenderUi plugin draws the menus
+    bundle.with_plugin(RenderUi::default())
+}
+
+/// Switches the window to fullscreen (for `--fullscreen`), on whichever monitor it opened on.
+///
+/// This is done once the window exists, rather than through `DisplayConfig`, because picking a
+/// monitor up front needs an event loop of its own.
+#[derive(Default)]
+pub struct FullscreenSystem {
+    done: bool,
+}
+
+impl<'s> System<'s> for FullscreenSystem {
+    type SystemData = ReadExpect<'s, Window>;
+
+    fn run(&mut self, window: Self::SystemData) {
+        if self.done {
+            return;
+        }
+
+        window.set_fullscreen(Some(window.get_current_monitor()));
+        self.done = true;
+    }
+}
@@ -0,0 +1,352 @@
+use amethyst::assets::AssetStorage;
+use amethyst::core::{SystemExt, Transform, TransformBundle};
+use amethyst::ecs::{Entity, Join, World};
+use amethyst::input::{is_close_requested, Bindings, InputBundle, StringBindings};
+use amethyst::prelude::Builder;
+use amethyst::renderer::light::{Light, PointLight};
+use amethyst::renderer::palette::rgb::Rgb;
+use amethyst::renderer::{types::DefaultBackend, Mesh};
+use amethyst::ui::UiBundle;
+use amethyst::utils::fps_counter::FpsCounterBundle;
+use amethyst::GameData;
+use amethyst::GameDataBuilder;
+use amethyst::SimpleState;
+use amethyst::StateData;
+use amethyst::{SimpleTrans, StateEvent, Trans};
+
+use log::{info, warn};
+
+use crate::autopilot::Pilot;
+use crate::camera::{self, FollowCameraSystem};
+use crate::collision::ShipCrashed;
+use crate::controls;
+use crate::cube_field::{self, Cube, CubeField};
+use crate::difficulty::Difficulty;
+use crate::game_over::GameOverState;
+use crate::gameplay_config::GameplayConfig;
+use crate::ghost::{self, GhostConfig};
+use crate::hud::{self, HudSystem};
+use crate::pause::PauseState;
+use crate::replay::{self, ReplayRecorder};
+use crate::rng::GameRng;
+use crate::score::Score;
+use crate::settings;
+use crate::ship;
+use crate::simulation::{InterpolationSystem, Simulation, SimulationTime};
+
+/// Whether a run is being played. The per-frame systems that follow the run (the camera and the
+/// HUD) are `pausable` on this resource, so they only do anything while it's `Running`; the
+/// `Simulation` itself only runs while the `GameState` is on top of the stack anyway.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gameplay {
+    Stopped,
+    Running,
+    Paused,
+}
+
+impl Default for Gameplay {
+    fn default() -> Self {
+        Gameplay::Stopped
+    }
+}
+
+/// A single run through the cube field.
+pub struct GameState {
+    /// Seed for the run's `GameRng`; playing the same seed again gives the exact same run.
+    pub seed: u64,
+    /// Ticks per second, if the run has to use a particular tick rate (like a replay does)
+    /// rather than the one in the `GameplayConfig`.
+    tick_rate: Option<u32>,
+    /// Whether to race against a ghost of the best run on this seed (if `GhostConfig` allows it).
+    ghost: bool,
+    /// Whether to show the HUD (if `HudConfig` allows it).
+    hud: bool,
+    /// Whether to switch to the `GameOverState` when the ship crashes, rather than just popping
+    /// off the stack.
+    game_over: bool,
+    simulation: Option<Simulation>,
+    /// Everything created in `on_start`, so `on_stop` can clean it up again.
+    scene: Vec<Entity>,
+}
+
+impl GameState {
+    pub fn new(seed: u64) -> Self {
+        GameState {
+            seed,
+            tick_rate: None,
+            ghost: true,
+            hud: true,
+            game_over: true,
+            simulation: None,
+            scene: Vec::new(),
+        }
+    }
+
+    pub fn with_tick_rate(mut self, tick_rate: u32) -> Self {
+        self.tick_rate = Some(tick_rate);
+        self
+    }
+
+    /// Never shows a ghost, whatever `GhostConfig` says; for runs that have to look the same every
+    /// time, like replays and captures.
+    pub fn without_ghost(mut self) -> Self {
+        self.ghost = false;
+        self
+    }
+
+    /// Never shows the HUD; for runs playing behind a menu.
+    pub fn without_hud(mut self) -> Self {
+        self.hud = false;
+        self
+    }
+
+    /// Pops off the stack as soon as the ship crashes, instead of showing the game-over screen;
+    /// for runs nobody is playing, like the attract mode's. Headless runs always do this.
+    pub fn without_game_over(mut self) -> Self {
+        self.game_over = false;
+        self
+    }
+
+    /// Runs the simulation ticks that are due this frame.
+    pub fn run_simulation(&mut self, world: &mut World) {
+        if let Some(simulation) = &mut self.simulation {
+            simulation.run(world);
+        }
+    }
+
+    /// Creates a ghost flying the best run on this seed, if there is one that was played at
+    /// `tick_rate` and with the current `GameplayConfig`; with anything else, it wouldn't fly
+    /// the same way it did.
+    fn initialize_ghost(&self, world: &mut World, tick_rate: u32) -> Option<Entity> {
+        let best = replay::load_best(self.seed)?;
+        if best.tick_rate != tick_rate {
+            info!(
+                "Not showing a ghost: the best run on seed {} was played at {} ticks per second",
+                self.seed, best.tick_rate
+            );
+            return None;
+        }
+        if !best.matches_config(&world.read_resource::<GameplayConfig>()) {
+            info!(
+                "Not showing a ghost: the best run on seed {} was played with different gameplay \
+                 settings",
+                self.seed
+            );
+            return None;
+        }
+
+        Some(ghost::initialize_ghost(world, best))
+    }
+
+    /// Saves the run that just ended as the last run, and as the best run on its seed if it is.
+    fn save_replay(&self, world: &World) {
+        let score = world.read_resource::<Score>().current();
+        let config = world.read_resource::<GameplayConfig>();
+        let replay = match world
+            .write_resource::<ReplayRecorder>()
+            .finish(score, &config)
+        {
+            Some(replay) => replay,
+            None => return,
+        };
+
+        let saved = replay::default_path().and_then(|path| settings::write(&replay, &path));
+        if let Err(error) = saved {
+            warn!("Couldn't save the replay: {}", error);
+        }
+        match replay::save_if_best(&replay) {
+            Ok(true) => info!("That was the best run on seed {} so far", self.seed),
+            Ok(false) => {}
+            Err(error) => warn!("Couldn't save the best run: {}", error),
+        }
+    }
+}
+
+impl SimpleState for GameState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        let world = state_data.world;
+
+        let tick_rate = self
+            .tick_rate
+            .unwrap_or_else(|| world.read_resource::<GameplayConfig>().simulation.tick_rate);
+
+        info!(
+            "Starting a run with seed {} at {} ticks per second",
+            self.seed, tick_rate
+        );
+        world.add_resource(Gameplay::Running);
+
+        // Headless runs don't have a `RenderingBundle`, so there's nothing to draw with
+        let render = rendering_enabled(world);
+        let (simulation, ship) = start_run(world, self.seed, tick_rate, render);
+        self.simulation = Some(simulation);
+        self.scene.push(ship);
+        if render {
+            self.scene.push(camera::initialize_camera(world, ship));
+            self.scene.push(initialize_light(world));
+            cube_field::initialize_cube_assets(world);
+
+            let ghost_enabled = world
+                .res
+                .try_fetch::<GhostConfig>()
+                .map_or(false, |config| config.enabled);
+            if self.ghost && ghost_enabled {
+                if let Some(ghost) = self.initialize_ghost(world, tick_rate) {
+                    self.scene.push(ghost);
+                }
+            }
+
+            if self.hud {
+                let hud = hud::initialize_hud(world);
+                self.scene.extend(hud.entities());
+                world.add_resource(hud);
+            }
+        }
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        let world = state_data.world;
+        world.add_resource(Gameplay::Stopped);
+        self.simulation = None;
+
+        let cubes: Vec<Entity> = (&world.entities(), &world.read_storage::<Cube>())
+            .join()
+            .map(|(entity, _)| entity)
+            .collect();
+        self.scene.extend(cubes);
+
+        world
+            .delete_entities(&self.scene)
+            .expect("Failed to clean up after a run");
+        self.scene.clear();
+    }
+
+    fn on_pause(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        state_data.world.add_resource(Gameplay::Paused);
+    }
+
+    fn on_resume(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        state_data.world.add_resource(Gameplay::Running);
+    }
+
+    fn handle_event(
+        &mut self,
+        _state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        match controls::action_pressed(&event) {
+            Some(controls::PAUSE) | Some(controls::BACK) => {
+                Trans::Push(Box::new(PauseState::new(self.seed)))
+            }
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, state_data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.run_simulation(state_data.world);
+
+        let world = &state_data.world;
+        if world.read_resource::<ShipCrashed>().0 {
+            // Only the player's own runs are kept; headless and autopilot runs (soak tests and
+            // demos) would just pile up, and they aren't the player's to race against
+            let keep_replay =
+                *world.read_resource::<Pilot>() == Pilot::Player && rendering_enabled(world);
+            if keep_replay {
+                self.save_replay(world);
+            }
+
+            // Headless runs never show a menu, whatever they were started with
+            if !self.game_over || !rendering_enabled(world) {
+                return Trans::Pop;
+            }
+            return Trans::Switch(Box::new(GameOverState::new(self.seed)));
+        }
+
+        Trans::None
+    }
+}
+
+/// Puts a fresh run on `seed` into the world, at `tick_rate` ticks per second: resets every
+/// resource the run keeps its state in, and creates the ship (with a mesh only if `render` is
+/// set). Returns the run's `Simulation` and the ship.
+///
+/// This is all a run needs to be simulated, so tests can drive the `Simulation` directly in a
+/// bare `World`, without an `Application` or any states.
+pub fn start_run(
+    world: &mut World,
+    seed: u64,
+    tick_rate: u32,
+    render: bool,
+) -> (Simulation, Entity) {
+    // The simulation's systems add any resources that aren't there yet, so it comes first
+    let simulation = Simulation::new(world);
+
+    world.add_resource(GameRng::from_seed(seed));
+    world.add_resource(ShipCrashed::default());
+    let config = world.read_resource::<GameplayConfig>().clone();
+    world.add_resource(CubeField::new(&config.cube_field));
+    world.add_resource(Difficulty::default());
+    world.add_resource(SimulationTime::new(tick_rate));
+    world.write_resource::<Score>().start_run();
+    world.add_resource(ReplayRecorder::new(seed, tick_rate, config));
+
+    let ship = ship::initialize_ship(world, render);
+    (simulation, ship)
+}
+
+/// Adds everything the game needs every frame to *play*, as opposed to draw. The gameplay itself
+/// runs at its own tick rate, in the `Simulation` each `GameState` creates.
+///
+/// Both the windowed game and `--headless` runs build their `GameDataBuilder` with this, so the
+/// gameplay logic is the same in both; only the windowed game adds a `RenderingBundle` (and a
+/// `GameplayConfigReloadSystem`) on top.
+pub fn with_gameplay_systems<'a, 'b>(
+    game_data: GameDataBuilder<'a, 'b>,
+    bindings: Bindings<StringBindings>,
+) -> amethyst::Result<GameDataBuilder<'a, 'b>> {
+    game_data
+        .with_bundle(InputBundle::<StringBindings>::new().with_bindings(bindings))?
+        .with(InterpolationSystem, "interpolation", &[])
+        .with(
+            FollowCameraSystem.pausable(Gameplay::Running),
+            "follow_camera",
+            &["interpolation"],
+        )
+        .with_bundle(TransformBundle::new().with_dep(&["interpolation", "follow_camera"]))
+}
+
+/// Adds the menus and the HUD. Only games with a window get these, since there's nothing to
+/// show them on in headless runs.
+pub fn with_ui_systems<'a, 'b>(
+    game_data: GameDataBuilder<'a, 'b>,
+) -> amethyst::Result<GameDataBuilder<'a, 'b>> {
+    Ok(game_data
+        .with_bundle(UiBundle::<DefaultBackend, StringBindings>::new())?
+        .with_bundle(FpsCounterBundle::default())?
+        .with(HudSystem.pausable(Gameplay::Running), "hud", &[]))
+}
+
+/// Whether the `RenderingBundle` was added to the game, i.e. whether we aren't headless.
+pub fn rendering_enabled(world: &World) -> bool {
+    world.res.has_value::<AssetStorage<Mesh>>()
+}
+
+fn initialize_light(world: &mut World) -> Entity {
+    let light: Light = PointLight {
+        intensity: 10.0,
+        color: Rgb::new(1.0, 1.0, 1.0),
+        ..PointLight::default()
+    }
+    .into();
+
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(5.0, 5.0, 20.0);
+
+    world.create_entity().with(light).with(transform).build()
+}
@@ -0,0 +1,144 @@
+use amethyst::ecs::Entity;
+use amethyst::input::is_close_requested;
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans};
+use log::{info, warn};
+
+use crate::autopilot::Pilot;
+use crate::game::GameState;
+use crate::high_scores::{self, HighScores};
+use crate::menu::{self, Menu, MenuAction};
+use crate::score::Score;
+
+const RETRY: usize = 0;
+const NEW_SEED: usize = 1;
+const MAIN_MENU: usize = 2;
+
+const SUMMARY_TOP: f32 = -110.0;
+const SUMMARY_SPACING: f32 = 36.0;
+const SUMMARY_FONT_SIZE: f32 = 28.0;
+
+/// Where the game ends up once the ship hits a cube: a summary of the run, and a menu to retry
+/// the same seed, play a new one, or go back to the main menu.
+pub struct GameOverState {
+    /// Seed of the run that just ended.
+    pub seed: u64,
+    menu: Option<Menu>,
+    summary: Vec<Entity>,
+}
+
+impl GameOverState {
+    pub fn new(seed: u64) -> Self {
+        GameOverState {
+            seed,
+            menu: None,
+            summary: Vec::new(),
+        }
+    }
+}
+
+impl SimpleState for GameOverState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        let world = state_data.world;
+
+        let score = world.read_resource::<Score>().current();
+        info!("Game over! Made it {:.0} units (seed {})", score, self.seed);
+
+        let high_scores_path = match high_scores::default_path() {
+            Ok(path) => Some(path),
+            Err(error) => {
+                warn!("Couldn't find the high score table: {}", error);
+                None
+            }
+        };
+
+        // Only the player's own runs make it into the high-score table
+        let rank = match &high_scores_path {
+            Some(path) if *world.read_resource::<Pilot>() == Pilot::Player => {
+                match high_scores::record_run(path, score, self.seed) {
+                    Ok(rank) => rank,
+                    Err(error) => {
+                        warn!("Couldn't save the high score table: {}", error);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        // The best run ever played on this machine, this one included, as opposed to the
+        // session's best that the HUD shows
+        let all_time_best = high_scores_path
+            .as_ref()
+            .and_then(|path| HighScores::load(path).best());
+
+        let mut lines = vec![format!("Distance: {:.0}", score)];
+        if let Some(best) = all_time_best {
+            lines.push(format!("All-time best: {:.0}", best));
+        }
+        lines.push(format!("Seed: {}", self.seed));
+        match rank {
+            Some(0) => {
+                info!("New high score!");
+                lines.push("New high score!".to_string());
+            }
+            Some(rank) => lines.push(format!("Ranked #{}", rank + 1)),
+            None => {}
+        }
+
+        self.menu = Some(Menu::create(
+            world,
+            "Game Over",
+            &["Retry Same Seed", "New Seed", "Main Menu"],
+        ));
+
+        let font = menu::default_font(world);
+        for (index, line) in lines.iter().enumerate() {
+            let y = SUMMARY_TOP - SUMMARY_SPACING * index as f32;
+            let id = format!("game_over_summary_{}", index);
+            self.summary.push(menu::create_text(
+                world,
+                &font,
+                &id,
+                line,
+                y,
+                SUMMARY_FONT_SIZE,
+            ));
+        }
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(menu) = self.menu.take() {
+            menu.delete(state_data.world);
+        }
+        state_data
+            .world
+            .delete_entities(&self.summary)
+            .expect("Failed to delete the run summary");
+        self.summary.clear();
+    }
+
+    fn handle_event(
+        &mut self,
+        state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        match self
+            .menu
+            .as_mut()
+            .and_then(|menu| menu.handle_event(state_data.world, &event))
+        {
+            Some(MenuAction::Confirm(RETRY)) => Trans::Switch(Box::new(GameState::new(self.seed))),
+            Some(MenuAction::Confirm(NEW_SEED)) => {
+                Trans::Switch(Box::new(GameState::new(rand::random())))
+            }
+            Some(MenuAction::Confirm(MAIN_MENU)) | Some(MenuAction::Back) => Trans::Pop,
+            _ => Trans::None,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use amethyst::config::Config;
+use amethyst::core::Time;
+use amethyst::ecs::{Read, System, Write};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::autopilot::AutopilotConfig;
+use crate::camera::FollowCameraConfig;
+use crate::cube_field::CubeFieldConfig;
+use crate::difficulty::DifficultyConfig;
+use crate::replay::ReplayPlayback;
+use crate::ship::ShipConfig;
+use crate::simulation::SimulationConfig;
+
+/// How often `GameplayConfigReloadSystem` checks the config file for changes, in seconds.
+const RELOAD_CHECK_INTERVAL: f32 = 0.5;
+
+/// Every gameplay tuning knob, from `assets/config/gameplay.ron`.
+///
+/// Systems should read their numbers from this resource instead of hard-coding them, so they
+/// can be tuned (even while the game is running) without recompiling.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GameplayConfig {
+    pub cube_field: CubeFieldConfig,
+    pub ship: ShipConfig,
+    pub difficulty: DifficultyConfig,
+    pub camera: FollowCameraConfig,
+    pub simulation: SimulationConfig,
+    pub autopilot: AutopilotConfig,
+}
+
+impl GameplayConfig {
+    /// Whether a run plays out exactly the same with `other` as with this config, tick for
+    /// tick. Only the camera and the autopilot are allowed to differ: neither changes what
+    /// happens, given the same steering.
+    pub fn plays_like(&self, other: &GameplayConfig) -> bool {
+        self.cube_field == other.cube_field
+            && self.ship == other.ship
+            && self.difficulty == other.difficulty
+            && self.simulation == other.simulation
+    }
+
+    /// Clamps every value the game can't run with (a negative spread, say) to the nearest one
+    /// it can, with a warning, so a typo in the file can't crash the game.
+    pub fn validate(&mut self) {
+        self.cube_field.validate();
+        self.ship.validate();
+        self.difficulty.validate();
+    }
+}
+
+/// Raises `value` to `min` if it's below it (or not a number), warning that `name` was out of
+/// range.
+pub fn clamp_at_least(name: &str, value: &mut f32, min: f32) {
+    if value.is_nan() || *value < min {
+        warn!(
+            "The gameplay config's {} is {}, using {} instead",
+            name, value, min
+        );
+        *value = min;
+    }
+}
+
+/// Loads the gameplay config from `path`, falling back to the defaults if it's missing or
+/// broken.
+pub fn load(path: &Path) -> GameplayConfig {
+    let mut config = GameplayConfig::load_no_fallback(path).unwrap_or_else(|error| {
+        warn!(
+            "Couldn't load the gameplay config from {:?}, using the defaults: {}",
+            path, error
+        );
+        GameplayConfig::default()
+    });
+    config.validate();
+    config
+}
+
+/// Reloads the `GameplayConfig` resource whenever its file changes on disk.
+///
+/// If the new file doesn't parse (which is likely while someone is halfway through editing it),
+/// the old config is kept and a warning is logged. Changes wait until no replay is playing,
+/// since the replay would play out differently with them.
+pub struct GameplayConfigReloadSystem {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+    seconds_since_check: f32,
+}
+
+impl GameplayConfigReloadSystem {
+    pub fn new(path: PathBuf) -> Self {
+        let last_modified = modified_time(&path);
+        GameplayConfigReloadSystem {
+            path,
+            last_modified,
+            seconds_since_check: 0.0,
+        }
+    }
+}
+
+impl<'s> System<'s> for GameplayConfigReloadSystem {
+    type SystemData = (
+        Write<'s, GameplayConfig>,
+        Read<'s, ReplayPlayback>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self, (mut config, playback, time): Self::SystemData) {
+        self.seconds_since_check += time.delta_real_seconds();
+        if self.seconds_since_check < RELOAD_CHECK_INTERVAL || playback.is_active() {
+            return;
+        }
+        self.seconds_since_check = 0.0;
+
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.last_modified {
+            return;
+        }
+        self.last_modified = modified;
+
+        match GameplayConfig::load_no_fallback(&self.path) {
+            Ok(mut new_config) => {
+                new_config.validate();
+                info!("Reloaded the gameplay config from {:?}", self.path);
+                *config = new_config;
+            }
+            Err(error) => warn!(
+                "Couldn't reload the gameplay config from {:?}, keeping the old one: {}",
+                self.path, error
+            ),
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ship::Ship;
+
+    #[test]
+    fn validate_clamps_what_the_game_cant_run_with() {
+        let mut config = GameplayConfig::default();
+        config.cube_field.spread = -5.0;
+        config.cube_field.density = std::f32::NAN;
+        config.ship.max_speed = 0.0;
+        config.ship.acceleration = -1.0;
+        config.validate();
+
+        assert_eq!(config.cube_field.spread, 0.0);
+        assert_eq!(config.cube_field.density, 0.0);
+        assert!(config.ship.max_speed > 0.0);
+        assert!(config.ship.acceleration > 0.0);
+
+        let ship = Ship { velocity: 3.0 };
+        assert!(ship.bank(&config.ship).is_finite());
+    }
+
+    #[test]
+    fn validate_keeps_the_defaults() {
+        let mut config = GameplayConfig::default();
+        config.validate();
+        let defaults = GameplayConfig::default();
+        assert_eq!(config.cube_field.spread, defaults.cube_field.spread);
+        assert_eq!(config.ship.max_speed, defaults.ship.max_speed);
+        assert_eq!(config.ship.acceleration, defaults.ship.acceleration);
+    }
+}
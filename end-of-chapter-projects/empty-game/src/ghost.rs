@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use amethyst::assets::{AssetLoaderSystemData, Handle};
+use amethyst::core::Transform;
+use amethyst::ecs::{
+    Component, DenseVecStorage, Entities, Entity, Join, Read, System, World, WriteStorage,
+};
+use amethyst::prelude::Builder;
+use amethyst::renderer::{
+    loaders::load_from_linear_rgba, palette::LinSrgba, transparent::Transparent, Hidden, Material,
+    MaterialDefaults, Texture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay_config::GameplayConfig;
+use crate::replay::{Replay, ReplayPlayback};
+use crate::ship::{self, Ship};
+use crate::simulation::{Interpolation, SimulationTime};
+
+/// The ghost's color, as `[Red, Green, Blue, Alpha]`. Mostly see-through, so it never hides a
+/// cube from the player.
+const GHOST_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 0.35];
+
+/// Contents of `config/ghost.ron`, or of the player's copy in the settings directory. The
+/// options menu turns the ghost on and off, and saves it to the player's copy.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GhostConfig {
+    /// Race against a ghost of the best run on the same seed, if there is one.
+    pub enabled: bool,
+}
+
+impl Default for GhostConfig {
+    fn default() -> Self {
+        GhostConfig { enabled: true }
+    }
+}
+
+/// Where the options menu saves the player's `GhostConfig` to.
+pub struct GhostConfigPath(pub PathBuf);
+
+/// A see-through copy of the ship, flying the best run on the current seed so the player can
+/// race against it.
+///
+/// The ghost isn't a `Ship`, so it doesn't collide with anything, and the player's steering
+/// doesn't move it. It steers itself with the recording instead, and disappears once the
+/// recording runs out (which is where that run crashed).
+pub struct Ghost {
+    ship: Ship,
+    playback: ReplayPlayback,
+}
+
+impl Component for Ghost {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Creates a ghost flying `replay`, starting where the ship does.
+pub fn initialize_ghost(world: &mut World, replay: Replay) -> Entity {
+    let mesh = ship::load_ship_mesh(world);
+    let material = load_ghost_material(world);
+
+    let mut playback = ReplayPlayback::default();
+    playback.start(replay);
+
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(0.0, 0.0, 0.0);
+
+    world
+        .create_entity()
+        .with(Ghost {
+            ship: Ship::default(),
+            playback,
+        })
+        .with(Interpolation::new(&transform))
+        .with(transform)
+        .with(mesh)
+        .with(material)
+        .with(Transparent)
+        .build()
+}
+
+/// A material of its own for the ghost, since the ship's is opaque. The see-through part comes
+/// from the alpha of its albedo, which only gets blended for `Transparent` entities.
+fn load_ghost_material(world: &mut World) -> Handle<Material> {
+    let [red, green, blue, alpha] = GHOST_COLOR;
+    let albedo = world.exec(|loader: AssetLoaderSystemData<'_, Texture>| {
+        loader.load_from_data(
+            load_from_linear_rgba(LinSrgba::new(red, green, blue, alpha)).into(),
+            (),
+        )
+    });
+
+    let material_defaults = world.read_resource::<MaterialDefaults>().0.clone();
+    world.exec(|loader: AssetLoaderSystemData<'_, Material>| {
+        loader.load_from_data(
+            Material {
+                albedo,
+                ..material_defaults
+            },
+            (),
+        )
+    })
+}
+
+/// Flies every ghost one tick further through its recording, and hides it once the recording
+/// has run out.
+pub struct GhostSystem;
+
+impl<'s> System<'s> for GhostSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, Ghost>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Hidden>,
+        Read<'s, GameplayConfig>,
+        Read<'s, SimulationTime>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut ghosts, mut transforms, mut hidden, config, time): Self::SystemData,
+    ) {
+        for (entity, ghost, transform) in (&entities, &mut ghosts, &mut transforms).join() {
+            match ghost.playback.next_steering() {
+                Some(steer) => ship::steer_ship(
+                    &mut ghost.ship,
+                    transform,
+                    steer,
+                    &config.ship,
+                    time.tick_seconds(),
+                ),
+                None if !hidden.contains(entity) => {
+                    hidden
+                        .insert(entity, Hidden)
+                        .expect("Failed to hide a ghost");
+                }
+                None => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::{Arc, Mutex};
+
+use amethyst::core::Time;
+use amethyst::ecs::{System, Write};
+use amethyst::shrev::EventChannel;
+use amethyst::{
+    GameData, SimpleState, SimpleTrans, State, StateData, StateEvent, Trans, TransEvent,
+};
+
+use crate::collision::ShipCrashed;
+use crate::score::Score;
+
+/// The frame time every headless frame pretends to have taken, regardless of how long it
+/// actually took.
+pub const FIXED_DELTA_SECONDS: f32 = 1.0 / 60.0;
+
+/// Overwrites the frame's delta time with a fixed value, so headless runs and captures are
+/// reproducible. The `Simulation` ticks at its own rate either way (one tick per frame, with
+/// `SimulationClock::OneTickPerFrame`); this is for the per-frame systems, like the camera.
+///
+/// This has to be the first system added to the `GameDataBuilder`, so that every system after
+/// it sees the fixed delta.
+pub struct FixedTimestepSystem {
+    pub delta_seconds: f32,
+}
+
+impl<'s> System<'s> for FixedTimestepSystem {
+    type SystemData = Write<'s, Time>;
+
+    fn run(&mut self, mut time: Self::SystemData) {
+        time.set_delta_seconds(self.delta_seconds);
+    }
+}
+
+/// How far a headless run got.
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessReport {
+    pub frames_run: u64,
+    /// The run's `Score`, as of the last frame.
+    pub distance: f32,
+    /// Whether the run ended with the ship hitting a cube.
+    pub crashed: bool,
+}
+
+impl HeadlessReport {
+    /// Exit status for a headless run: `0` if it played out (every requested frame was
+    /// simulated, or the ship crashed before then), `1` otherwise.
+    pub fn exit_status(&self, frames: u64) -> i32 {
+        if self.frames_run >= frames || self.crashed {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Sits at the bottom of the state stack in headless mode.
+///
+/// It pushes the state to run (a `GameState`, or a `ReplayState`) on its first update, keeps
+/// the `HeadlessReport` up to date while the game runs on top of it, and quits the application
+/// once the requested number of frames has been simulated (or as soon as the state on top pops
+/// itself off the stack, like a `GameState` without a game-over screen does when the ship
+/// crashes).
+pub struct HeadlessState {
+    frames: u64,
+    run: Option<Box<dyn State<GameData<'static, 'static>, StateEvent>>>,
+    report: Arc<Mutex<HeadlessReport>>,
+    /// Whether the run is on the stack; frames only count while it is.
+    running: bool,
+}
+
+impl HeadlessState {
+    /// `report` is shared with `main()`, so it can tell how far the run got after
+    /// `Application::run` returns.
+    pub fn new(
+        frames: u64,
+        run: Box<dyn State<GameData<'static, 'static>, StateEvent>>,
+        report: Arc<Mutex<HeadlessReport>>,
+    ) -> Self {
+        HeadlessState {
+            frames,
+            run: Some(run),
+            report,
+            running: false,
+        }
+    }
+}
+
+impl SimpleState for HeadlessState {
+    fn on_pause(&mut self, _state_data: StateData<'_, GameData<'_, '_>>) {
+        self.running = true;
+    }
+
+    fn on_resume(&mut self, _state_data: StateData<'_, GameData<'_, '_>>) {
+        // The run popped itself off the stack, so the frame it takes to quit isn't part of it
+        self.running = false;
+    }
+
+    fn update(&mut self, _state_data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        match self.run.take() {
+            Some(run) => Trans::Push(run),
+            None => Trans::Quit,
+        }
+    }
+
+    fn shadow_update(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if !self.running {
+            return;
+        }
+
+        let world = state_data.world;
+        let frames_run = {
+            let mut report = self
+                .report
+                .lock()
+                .expect("The headless report was poisoned");
+            report.frames_run += 1;
+            // A replay that was refused never starts its run, so it has no score to report
+            report.distance = world
+                .res
+                .try_fetch::<Score>()
+                .map_or(0.0, |score| score.current());
+            report.crashed = world
+                .res
+                .try_fetch::<ShipCrashed>()
+                .map_or(false, |crashed| crashed.0);
+            report.frames_run
+        };
+
+        if frames_run == self.frames {
+            world
+                .write_resource::<EventChannel<TransEvent<GameData<'static, 'static>, StateEvent>>>(
+                )
+                .single_write(Box::new(|| Trans::Quit));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autopilot::Pilot;
+    use crate::game::GameState;
+    use crate::gameplay_config::GameplayConfig;
+    use crate::test_support::{self, TestRun, MAX_TICKS};
+
+    const TICKS: u64 = 600;
+
+    #[test]
+    fn simulation_runs_without_a_window() {
+        let mut run = TestRun::new(GameplayConfig::default(), 1, Pilot::Player);
+        run.run(TICKS);
+
+        assert!(run.ticks() == TICKS || run.crashed());
+        assert!(run.score() > 0.0);
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_run() {
+        let mut first = TestRun::new(GameplayConfig::default(), 7, Pilot::Autopilot);
+        let mut second = TestRun::new(GameplayConfig::default(), 7, Pilot::Autopilot);
+        first.run(TICKS);
+        second.run(TICKS);
+
+        assert_eq!(first.ticks(), second.ticks());
+        assert_eq!(first.crashed(), second.crashed());
+        assert_eq!(first.score().to_bits(), second.score().to_bits());
+    }
+
+    #[test]
+    fn run_headless_runs_the_requested_frames() {
+        let frames = 60;
+        let report = test_support::run_headless(
+            frames,
+            GameplayConfig::default(),
+            Pilot::Autopilot,
+            Box::new(GameState::new(1).without_game_over()),
+        );
+
+        assert_eq!(report.frames_run, frames);
+        assert!(!report.crashed);
+        assert!(report.distance > 0.0);
+        assert_eq!(report.exit_status(frames), 0);
+    }
+
+    #[test]
+    fn run_headless_counts_frames_up_to_the_crash() {
+        let mut run = TestRun::new(GameplayConfig::default(), 5, Pilot::Player);
+        run.run(MAX_TICKS);
+        assert!(run.crashed());
+
+        let report = test_support::run_headless(
+            MAX_TICKS,
+            GameplayConfig::default(),
+            Pilot::Player,
+            Box::new(GameState::new(5).without_game_over()),
+        );
+
+        assert!(report.crashed);
+        assert_eq!(report.frames_run, run.ticks());
+        assert_eq!(report.exit_status(MAX_TICKS), 0);
+    }
+
+    #[test]
+    fn unfinished_run_fails() {
+        let report = HeadlessReport {
+            frames_run: 10,
+            ..HeadlessReport::default()
+        };
+        assert_eq!(report.exit_status(10), 0);
+        assert_eq!(report.exit_status(600), 1);
+    }
+}
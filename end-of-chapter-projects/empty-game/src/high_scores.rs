@@ -0,0 +1,257 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use amethyst::utils::application_root_dir;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::settings;
+
+/// How many entries the high-score table keeps.
+pub const MAX_ENTRIES: usize = 10;
+
+/// Name that runs are recorded under until players can enter their own.
+pub const DEFAULT_PLAYER_NAME: &str = "Player";
+
+/// One run in the high-score table.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub score: f32,
+    /// Seed the run was played with, so it can be played again.
+    pub seed: u64,
+    pub date: DateTime<Utc>,
+}
+
+/// The best runs ever played on this machine, best first.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HighScores {
+    entries: Vec<HighScoreEntry>,
+}
+
+impl HighScores {
+    /// Loads the table from `path`.
+    ///
+    /// A missing file just means nobody has played yet, and a corrupted one isn't worth crashing
+    /// the game over; both start a fresh, empty table.
+    pub fn load(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(error) => {
+                warn!(
+                    "Couldn't read high scores from {:?}, starting fresh: {}",
+                    path, error
+                );
+                return Self::default();
+            }
+        };
+
+        match ron::de::from_str::<HighScores>(&contents) {
+            Ok(mut high_scores) => {
+                // The file might have been edited by hand, so don't trust its order or length
+                high_scores.sort_and_truncate();
+                high_scores
+            }
+            Err(error) => {
+                warn!(
+                    "High scores in {:?} are corrupted, starting fresh: {}",
+                    path, error
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// All entries, best first.
+    pub fn entries(&self) -> &[HighScoreEntry] {
+        &self.entries
+    }
+
+    /// The best score in the table, if there's anything in it.
+    pub fn best(&self) -> Option<f32> {
+        self.entries.first().map(|entry| entry.score)
+    }
+
+    /// Whether `score` is good enough to make it into the table.
+    pub fn qualifies(&self, score: f32) -> bool {
+        self.entries.len() < MAX_ENTRIES
+            || self
+                .entries
+                .last()
+                .map_or(true, |lowest| score > lowest.score)
+    }
+
+    /// Adds `entry` to the table if it's good enough, dropping the lowest entry if the table is
+    /// full. Returns the entry's rank (`0` is the best), or `None` if it didn't make it.
+    ///
+    /// Ties go to the older entry.
+    pub fn insert(&mut self, entry: HighScoreEntry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+
+        let rank = self
+            .entries
+            .iter()
+            .position(|existing| entry.score > existing.score)
+            .unwrap_or_else(|| self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    fn sort_and_truncate(&mut self) {
+        // `sort_by` is stable, so equal scores stay in file order
+        self.entries.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
+
+/// Records a finished run in the table at `path`, under the default player name.
+///
+/// Returns the run's rank in the table, or `None` if it wasn't good enough to make it in.
+pub fn record_run(path: &Path, score: f32, seed: u64) -> amethyst::Result<Option<usize>> {
+    let mut high_scores = HighScores::load(path);
+    let rank = high_scores.insert(HighScoreEntry {
+        name: DEFAULT_PLAYER_NAME.to_string(),
+        score,
+        seed,
+        date: Utc::now(),
+    });
+
+    if rank.is_some() {
+        settings::write(&high_scores, path)?;
+    }
+    Ok(rank)
+}
+
+/// Where the high-score table lives: `high_scores.ron` in the application root.
+pub fn default_path() -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?.join("high_scores.ron"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: f32, seed: u64) -> HighScoreEntry {
+        HighScoreEntry {
+            name: DEFAULT_PLAYER_NAME.to_string(),
+            score,
+            seed,
+            date: Utc::now(),
+        }
+    }
+
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "empty-game-high-scores-{}-{}.ron",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn seeds(high_scores: &HighScores) -> Vec<u64> {
+        high_scores
+            .entries()
+            .iter()
+            .map(|entry| entry.seed)
+            .collect()
+    }
+
+    #[test]
+    fn missing_file_loads_empty() {
+        let path = scratch_path("missing");
+        let _ = fs::remove_file(&path);
+        assert!(HighScores::load(&path).entries().is_empty());
+    }
+
+    #[test]
+    fn corrupt_file_loads_empty() {
+        let path = scratch_path("corrupt");
+        fs::write(&path, "(entries: [this isn't ron").unwrap();
+        let high_scores = HighScores::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(high_scores.entries().is_empty());
+    }
+
+    #[test]
+    fn saved_table_loads_back() {
+        let path = scratch_path("round-trip");
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry(10.0, 1));
+        high_scores.insert(entry(20.0, 2));
+        settings::write(&high_scores, &path).unwrap();
+
+        let loaded = HighScores::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(seeds(&loaded), vec![2, 1]);
+    }
+
+    #[test]
+    fn insert_keeps_best_first_and_returns_the_rank() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.insert(entry(10.0, 1)), Some(0));
+        assert_eq!(high_scores.insert(entry(30.0, 2)), Some(0));
+        assert_eq!(high_scores.insert(entry(20.0, 3)), Some(1));
+        assert_eq!(high_scores.insert(entry(5.0, 4)), Some(3));
+        assert_eq!(seeds(&high_scores), vec![2, 3, 1, 4]);
+    }
+
+    #[test]
+    fn best_is_the_top_entry() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.best(), None);
+
+        high_scores.insert(entry(10.0, 1));
+        high_scores.insert(entry(30.0, 2));
+        high_scores.insert(entry(20.0, 3));
+        assert_eq!(high_scores.best(), Some(30.0));
+    }
+
+    #[test]
+    fn ties_go_to_the_older_entry() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry(10.0, 1));
+        assert_eq!(high_scores.insert(entry(10.0, 2)), Some(1));
+        assert_eq!(seeds(&high_scores), vec![1, 2]);
+    }
+
+    #[test]
+    fn full_table_drops_the_lowest_entry() {
+        let mut high_scores = HighScores::default();
+        for seed in 0..MAX_ENTRIES as u64 {
+            high_scores.insert(entry(10.0 + seed as f32, seed));
+        }
+        assert_eq!(high_scores.entries().len(), MAX_ENTRIES);
+
+        assert_eq!(high_scores.insert(entry(100.0, 100)), Some(0));
+        assert_eq!(high_scores.entries().len(), MAX_ENTRIES);
+        assert_eq!(high_scores.entries()[0].seed, 100);
+        assert!(high_scores.entries().iter().all(|entry| entry.seed != 0));
+    }
+
+    #[test]
+    fn qualifies_only_above_the_lowest_entry_once_full() {
+        let mut high_scores = HighScores::default();
+        for seed in 0..MAX_ENTRIES as u64 - 1 {
+            high_scores.insert(entry(10.0 + seed as f32, seed));
+        }
+        // One slot left, so anything makes it in
+        assert!(high_scores.qualifies(0.0));
+
+        high_scores.insert(entry(10.0, 99));
+        assert!(!high_scores.qualifies(9.0));
+        assert!(!high_scores.qualifies(10.0));
+        assert!(high_scores.qualifies(10.5));
+        assert_eq!(high_scores.insert(entry(10.0, 100)), None);
+        assert_eq!(high_scores.entries().len(), MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,92 @@
+use amethyst::ecs::Entity;
+use amethyst::input::is_close_requested;
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans};
+use log::warn;
+
+use crate::controls;
+use crate::high_scores::{self, HighScores};
+use crate::menu;
+
+const LINE_SPACING: f32 = 40.0;
+
+/// Shows the high-score table. "back" or "confirm" goes back to whatever pushed it.
+#[derive(Default)]
+pub struct HighScoresState {
+    text: Vec<Entity>,
+}
+
+impl SimpleState for HighScoresState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        let world = state_data.world;
+
+        let high_scores = match high_scores::default_path() {
+            Ok(path) => HighScores::load(&path),
+            Err(error) => {
+                warn!("Couldn't find the high score table: {}", error);
+                HighScores::default()
+            }
+        };
+
+        let mut lines: Vec<String> = high_scores
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(rank, entry)| {
+                format!(
+                    "{}. {}  {:.0}  (seed {}, {})",
+                    rank + 1,
+                    entry.name,
+                    entry.score,
+                    entry.seed,
+                    entry.date.format("%Y-%m-%d"),
+                )
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push("No high scores yet!".to_string());
+        }
+        lines.push(String::new());
+        lines.push("Press Back to return".to_string());
+
+        let font = menu::default_font(world);
+        self.text.push(menu::create_text(
+            world,
+            &font,
+            "high_scores_title",
+            "High Scores",
+            300.0,
+            60.0,
+        ));
+        for (index, line) in lines.iter().enumerate() {
+            let y = 220.0 - LINE_SPACING * index as f32;
+            let id = format!("high_scores_line_{}", index);
+            self.text
+                .push(menu::create_text(world, &font, &id, line, y, 28.0));
+        }
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        state_data
+            .world
+            .delete_entities(&self.text)
+            .expect("Failed to delete the high score table");
+        self.text.clear();
+    }
+
+    fn handle_event(
+        &mut self,
+        _state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        match controls::action_pressed(&event) {
+            Some(controls::BACK) | Some(controls::CONFIRM) => Trans::Pop,
+            _ => Trans::None,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::PathBuf;
+
+use amethyst::ecs::{Entity, Read, System, World, WriteStorage};
+use amethyst::prelude::Builder;
+use amethyst::ui::{Anchor, FontHandle, UiText, UiTransform};
+use amethyst::utils::fps_counter::FpsCounter;
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::Difficulty;
+use crate::gameplay_config::GameplayConfig;
+use crate::menu;
+use crate::score::Score;
+
+const HUD_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+const HUD_FONT_SIZE: f32 = 24.0;
+const HUD_MARGIN: f32 = 16.0;
+const HUD_LINE_SPACING: f32 = 30.0;
+const HUD_WIDTH: f32 = 300.0;
+
+/// Contents of `config/hud.ron`, or of the player's copy in the settings directory. The options
+/// menu changes it while the game runs, and saves it to the player's copy.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HudConfig {
+    /// Show the distance, best score and speed during a run.
+    pub visible: bool,
+    /// Also show the frame rate.
+    pub show_fps: bool,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        HudConfig {
+            visible: true,
+            show_fps: false,
+        }
+    }
+}
+
+/// Where the options menu saves the player's `HudConfig` to.
+pub struct HudConfigPath(pub PathBuf);
+
+/// The HUD's text entities, for the current run.
+pub struct Hud {
+    distance: Entity,
+    best: Entity,
+    speed: Entity,
+    fps: Entity,
+}
+
+impl Hud {
+    /// Everything the HUD is made of, so it can be cleaned up with the rest of the run.
+    pub fn entities(&self) -> Vec<Entity> {
+        vec![self.distance, self.best, self.speed, self.fps]
+    }
+}
+
+/// Creates the HUD's (empty, for now) lines of text in the top-left corner of the screen.
+/// `HudSystem` fills them in every frame.
+pub fn initialize_hud(world: &mut World) -> Hud {
+    let font = menu::default_font(world);
+    let mut line = 0;
+    let mut create_line = |world: &mut World, id: &str| {
+        let entity = create_hud_text(world, &font, id, line);
+        line += 1;
+        entity
+    };
+
+    Hud {
+        distance: create_line(world, "hud_distance"),
+        best: create_line(world, "hud_best"),
+        speed: create_line(world, "hud_speed"),
+        fps: create_line(world, "hud_fps"),
+    }
+}
+
+fn create_hud_text(world: &mut World, font: &FontHandle, id: &str, line: usize) -> Entity {
+    let transform = UiTransform::new(
+        id.to_string(),
+        Anchor::TopLeft,
+        Anchor::TopLeft,
+        HUD_MARGIN,
+        -HUD_MARGIN - HUD_LINE_SPACING * line as f32,
+        1.0,
+        HUD_WIDTH,
+        HUD_FONT_SIZE * 1.25,
+    );
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(UiText::new(
+            font.clone(),
+            String::new(),
+            HUD_COLOR,
+            HUD_FONT_SIZE,
+        ))
+        .build()
+}
+
+/// Keeps the HUD's text up to date with the current run, and hides whatever `HudConfig` says
+/// shouldn't be shown.
+pub struct HudSystem;
+
+impl<'s> System<'s> for HudSystem {
+    type SystemData = (
+        Option<Read<'s, Hud>>,
+        WriteStorage<'s, UiText>,
+        Read<'s, HudConfig>,
+        Read<'s, Score>,
+        Read<'s, Difficulty>,
+        Read<'s, GameplayConfig>,
+        Read<'s, FpsCounter>,
+    );
+
+    fn run(
+        &mut self,
+        (hud, mut texts, hud_config, score, difficulty, config, fps_counter): Self::SystemData,
+    ) {
+        let hud = match hud {
+            Some(hud) => hud,
+            None => return,
+        };
+
+        let visible = hud_config.visible;
+        let lines = [
+            (
+                hud.distance,
+                visible,
+                format!("Distance: {:.0}", score.current()),
+            ),
+            (
+                hud.best,
+                visible,
+                format!("Session best: {:.0}", score.best()),
+            ),
+            (
+                hud.speed,
+                visible,
+                format!("Speed: {:.1}", difficulty.speed(&config.cube_field)),
+            ),
+            (
+                hud.fps,
+                hud_config.show_fps,
+                format!("FPS: {:.0}", fps_counter.sampled_fps()),
+            ),
+        ];
+
+        for (entity, shown, text) in lines.iter() {
+            if let Some(ui_text) = texts.get_mut(*entity) {
+                ui_text.text = if *shown { text.clone() } else { String::new() };
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use amethyst::config::Config;
+use amethyst::{Logger, LoggerConfig};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::Args;
+
+/// Contents of `config/logger.ron`. The `--log-*` flags override it.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Most verbose log messages to show, unless `modules` says otherwise.
+    pub level: LevelFilter,
+    /// Per-module overrides of `level`, like `("amethyst_assets", Warn)`.
+    pub modules: Vec<(String, LevelFilter)>,
+    /// Also write the log to this file. Relative paths are relative to the app root.
+    pub file: Option<PathBuf>,
+    /// Only show warnings and errors from Amethyst and everything else that isn't the game
+    /// itself, while the game's own messages still go by `level`.
+    pub quiet_engine: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: LevelFilter::Info,
+            modules: Vec::new(),
+            file: None,
+            quiet_engine: false,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Applies the `--log-*` flags on top of the config file.
+    pub fn override_with(&mut self, args: &Args) {
+        if let Some(level) = args.log_level {
+            self.level = level;
+        }
+        self.modules.extend(args.log_module.iter().cloned());
+        if let Some(file) = &args.log_file {
+            self.file = Some(file.clone());
+        }
+        if args.quiet_engine {
+            self.quiet_engine = true;
+        }
+    }
+}
+
+/// Starts the logger; nothing logged before this shows up anywhere.
+///
+/// `game_module` is the game's root module path (`module_path!()` in `main.rs`), which is what
+/// `quiet_engine` keeps at the full `level`.
+pub fn start_logger(config: LoggingConfig, app_root: &Path, game_module: &'static str) {
+    let level_filter = if config.quiet_engine {
+        config.level.min(LevelFilter::Warn)
+    } else {
+        config.level
+    };
+
+    let mut logger = Logger::from_config(LoggerConfig {
+        level_filter,
+        log_file: config.file.map(|file| app_root.join(file)),
+        ..LoggerConfig::default()
+    });
+    if config.quiet_engine {
+        logger = logger.level_for(game_module, config.level);
+    }
+    // Module overrides come last, so they win over `quiet_engine`
+    for (module, level) in config.modules {
+        logger = logger.level_for(module, level);
+    }
+    logger.start();
+}
+
+/// Loads `config/logger.ron`, falling back to the defaults. This happens before the logger is
+/// started, so a broken file can only be reported on stderr.
+pub fn load_logging_config(path: &Path) -> LoggingConfig {
+    LoggingConfig::load_no_fallback(path).unwrap_or_else(|error| {
+        if path.exists() {
+            eprintln!(
+                "Couldn't load the logger config from {:?}, using the defaults: {}",
+                path, error
+            );
+        }
+        LoggingConfig::default()
+    })
+}
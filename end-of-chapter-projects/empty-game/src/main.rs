@@ -1,48 +1,334 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use amethyst::config::Config;
+use amethyst::core::frame_limiter::FrameRateLimitStrategy;
+use amethyst::input::{Bindings, StringBindings};
 use amethyst::utils::application_root_dir;
-use amethyst::SimpleState;
+use amethyst::window::ScreenDimensions;
 use amethyst::GameDataBuilder;
+use amethyst::{GameData, State, StateEvent};
 use amethyst::Application;
-use amethyst::renderer::{
-    plugins::{RenderShaded3D, RenderToWindow},
-    types::DefaultBackend,
-    RenderingBundle,
-};
-use amethyst::window::DisplayConfig;
+use log::error;
+
+mod attract;
+mod autopilot;
+mod camera;
+mod capture;
+mod cli;
+mod collision;
+mod controls;
+mod controls_menu;
+mod cube_field;
+mod difficulty;
+mod display;
+mod game;
+mod game_over;
+mod gameplay_config;
+mod ghost;
+mod headless;
+mod high_scores;
+mod high_scores_menu;
+mod hud;
+mod logging;
+mod main_menu;
+mod menu;
+mod offscreen;
+mod options;
+mod pause;
+mod replay;
+mod rng;
+mod score;
+mod settings;
+mod ship;
+mod simulation;
+#[cfg(test)]
+mod test_support;
+
+use crate::autopilot::Pilot;
+use crate::capture::{CaptureOptions, CaptureState};
+use crate::cli::Args;
+use crate::controls::BindingsPath;
+use crate::display::{FullscreenSystem, RenderConfig};
+use crate::game::GameState;
+use crate::gameplay_config::{GameplayConfig, GameplayConfigReloadSystem};
+use crate::ghost::{GhostConfig, GhostConfigPath};
+use crate::headless::{FixedTimestepSystem, HeadlessReport, HeadlessState};
+use crate::hud::{HudConfig, HudConfigPath};
+use crate::main_menu::MainMenuState;
+use crate::offscreen::FrameReadback;
+use crate::replay::{Replay, ReplayState};
+use crate::rng::RngConfig;
+use crate::simulation::SimulationClock;
 
-struct GameState;
-impl SimpleState for GameState {}
+/// How many frames `--headless` simulates when `--frames` isn't given.
+const DEFAULT_HEADLESS_FRAMES: u64 = 600;
 
 fn main() -> amethyst::Result<()> {
+    let args = Args::parse();
+
+    let app_root = application_root_dir()?;
+
+    // Everything configurable lives in `config/`
+    let config_dir = args
+        .config
+        .clone()
+        .unwrap_or_else(|| app_root.join("config"));
+
     // Set up the Amethyst logger
-    amethyst::start_logger(Default::default());
+    let mut logging_config = logging::load_logging_config(&config_dir.join("logger.ron"));
+    logging_config.override_with(&args);
+    logging::start_logger(logging_config, &app_root, module_path!());
 
     // Set up the assets directory (PathBuf)
-    let app_root = application_root_dir()?;
-    let assets_dir = app_root.join("assets");
+    let assets_dir = args
+        .assets_dir
+        .clone()
+        .unwrap_or_else(|| app_root.join("assets"));
 
-    // Set up the display configuration
-    let display_config = DisplayConfig {
-        title: "Cubefield".to_string(),
-        dimensions: Some((1024, 768)),
-        ..Default::default()
+    // Gameplay tuning lives with the assets, so it can be reloaded while the game runs
+    let gameplay_config_path = assets_dir.join("config").join("gameplay.ron");
+    let mut gameplay_config = gameplay_config::load(&gameplay_config_path);
+
+    // `--difficulty STAGE` plays the whole run at one stage of the difficulty curve
+    if let Some(stage) = &args.difficulty {
+        gameplay_config
+            .difficulty
+            .hold_stage(stage)
+            .map_err(amethyst::Error::from_string)?;
+    }
+
+    // `--autopilot` lets the `AutopilotSystem` steer instead of the player
+    let pilot = if args.autopilot {
+        Pilot::Autopilot
+    } else {
+        Pilot::Player
+    };
+
+    // Set up the input bindings (keyboard and gamepad controls). The controls menu saves the
+    // player's changes to the settings directory, and those are loaded over the defaults.
+    let bindings_path = config_dir.join("bindings.ron");
+    let user_bindings_path = settings::user_dir(&app_root).join("bindings.ron");
+
+    // `--seed N` wins over `config/rng.ron`; if neither has a seed, runs pick a random one
+    let rng_config = RngConfig::load(config_dir.join("rng.ron"));
+    let seed = args.seed.or(rng_config.seed);
+
+    // `--replay PATH` plays back a recorded run, instead of the player playing a new one
+    let replay = match &args.replay {
+        Some(path) => Some(Replay::load(path).map_err(|error| {
+            amethyst::Error::from_string(format!(
+                "Couldn't load the replay from {:?}: {}",
+                path, error
+            ))
+        })?),
+        None => None,
     };
 
-    // Set up the GameDataBuilder
-    let game_data = GameDataBuilder::default()
-        .with_bundle(
-            RenderingBundle::<DefaultBackend>::new()
-                // The RenderToWindow plugin provides all the scaffolding for opening a window and drawing on it
-                .with_plugin(
-                    RenderToWindow::from_config(display_config)
-                        .with_clear([0.95, 0.95, 0.95, 1.0]),
-                )
-                // RenderFlat2D plugin is used to render entities with a `SpriteRender` component.
-                .with_plugin(RenderShaded3D::default()),
+    // `--headless [--frames N]` runs the game without a window, then exits
+    if args.headless {
+        let status = match replay {
+            // A replay recorded with other gameplay settings can't play out the same way
+            Some(ref replay) if !replay.can_play_with(&gameplay_config) => 1,
+            // A replay ends by itself, and passes if it played out exactly as recorded
+            Some(replay) => {
+                let matched = Arc::new(AtomicBool::new(false));
+                run_headless(
+                    assets_dir,
+                    controls::load_bindings(&bindings_path, None)?,
+                    gameplay_config,
+                    pilot,
+                    u64::max_value(),
+                    Box::new(ReplayState::new(replay, matched.clone())),
+                )?;
+                if matched.load(Ordering::SeqCst) {
+                    0
+                } else {
+                    1
+                }
+            }
+            None => {
+                let frames = args.frames.unwrap_or(DEFAULT_HEADLESS_FRAMES);
+                let seed = seed.unwrap_or_else(rand::random);
+                let report = run_headless(
+                    assets_dir,
+                    controls::load_bindings(&bindings_path, None)?,
+                    gameplay_config,
+                    pilot,
+                    frames,
+                    Box::new(GameState::new(seed).without_game_over()),
+                )?;
+
+                // One line for scripts to read, whatever the log level
+                println!(
+                    "seed={} distance={:.1} crashed={} frames={}",
+                    seed, report.distance, report.crashed, report.frames_run
+                );
+                report.exit_status(frames)
+            }
+        };
+        std::process::exit(status);
+    }
+
+    // Set up the display configuration
+    let display_config = display::load_display_config(&config_dir.join("display.ron"));
+    let mut render_config = RenderConfig::load(config_dir.join("render.ron"));
+
+    // `--render flat|shaded|pbr` wins over `config/render.ron`
+    if let Some(pipeline) = args.render {
+        render_config.pipeline = pipeline;
+    }
+
+    // `--capture PATH [--frames N]` plays a run, drawing it offscreen, saves one frame to a PNG,
+    // then exits
+    if let Some(path) = args.capture.clone() {
+        let options = CaptureOptions {
+            path,
+            frames: args.frames.unwrap_or(capture::DEFAULT_FRAMES),
+            seed: seed.unwrap_or(capture::DEFAULT_SEED),
+        };
+        let captured = run_capture(
+            assets_dir,
+            controls::load_bindings(&bindings_path, None)?,
+            gameplay_config,
+            capture::dimensions(&display_config),
+            &render_config,
+            options,
         )?;
+        if !captured {
+            error!("The run ended before the frame could be captured");
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    // The HUD can be turned on and off from the options menu, which saves the player's choice
+    // to the settings directory rather than over the defaults
+    let hud_config_path = settings::user_dir(&app_root).join("hud.ron");
+    let hud_config: HudConfig = settings::load(&config_dir.join("hud.ron"), &hud_config_path);
+
+    // So can the ghost
+    let ghost_config_path = settings::user_dir(&app_root).join("ghost.ron");
+    let ghost_config: GhostConfig =
+        settings::load(&config_dir.join("ghost.ron"), &ghost_config_path);
+
+    // Set up the GameDataBuilder. Only the windowed game reloads the gameplay config when it
+    // changes; headless runs and captures have to play out the same every time, and keep any
+    // `--difficulty` hold.
+    let game_data = GameDataBuilder::default().with(
+        GameplayConfigReloadSystem::new(gameplay_config_path),
+        "gameplay_config_reload",
+        &[],
+    );
+    let bindings = controls::load_bindings(&bindings_path, Some(&user_bindings_path))?;
+    let game_data = game::with_gameplay_systems(game_data, bindings)?;
+    let game_data = game::with_ui_systems(game_data)?
+        .with_bundle(display::rendering_bundle(display_config, &render_config))?;
+    let game_data = if args.fullscreen {
+        game_data.with(FullscreenSystem::default(), "fullscreen", &[])
+    } else {
+        game_data
+    };
 
     // Run the game!
-    let mut game = Application::new(assets_dir, GameState, game_data)?;
+    let mut main_menu = MainMenuState::new(seed);
+    if let Some(replay) = replay {
+        main_menu = main_menu.with_replay(replay);
+    }
+    let mut game = Application::build(assets_dir, main_menu)?
+        .with_resource(gameplay_config)
+        .with_resource(BindingsPath(user_bindings_path))
+        .with_resource(hud_config)
+        .with_resource(HudConfigPath(hud_config_path))
+        .with_resource(ghost_config)
+        .with_resource(GhostConfigPath(ghost_config_path))
+        .with_resource(SimulationClock::RealTime)
+        .with_resource(pilot)
+        .build(game_data)?;
     game.run();
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Runs `run` (a `GameState` or `ReplayState`) for `frames` frames with a fixed timestep and no
+/// rendering bundle, so it works on machines without a GPU or display. Returns how far it got.
+///
+/// `gameplay_config` is used as it is for the whole run, even if its file changes meanwhile.
+fn run_headless(
+    assets_dir: PathBuf,
+    bindings: Bindings<StringBindings>,
+    gameplay_config: GameplayConfig,
+    pilot: Pilot,
+    frames: u64,
+    run: Box<dyn State<GameData<'static, 'static>, StateEvent>>,
+) -> amethyst::Result<HeadlessReport> {
+    let game_data = GameDataBuilder::default().with(
+        FixedTimestepSystem {
+            delta_seconds: headless::FIXED_DELTA_SECONDS,
+        },
+        "fixed_timestep",
+        &[],
+    );
+    let game_data = game::with_gameplay_systems(game_data, bindings)?;
+
+    let report = Arc::new(Mutex::new(HeadlessReport::default()));
+    let state = HeadlessState::new(frames, run, report.clone());
+
+    // Nothing is drawn, so there's no reason to wait between frames
+    let mut game = Application::build(assets_dir, state)?
+        .with_frame_limit(FrameRateLimitStrategy::Unlimited, 0)
+        .with_resource(gameplay_config)
+        .with_resource(SimulationClock::OneTickPerFrame)
+        .with_resource(pilot)
+        .build(game_data)?;
+    game.run();
+
+    let report = report.lock().expect("The headless report was poisoned");
+    Ok(report.clone())
+}
+
+/// Plays a run with a fixed timestep, like `run_headless` but drawing it into a `dimensions`
+/// sized image instead of a window, and captures a frame once `options.frames` frames have been
+/// simulated. Returns whether the capture was taken.
+///
+/// The autopilot flies, so the run plays out the same every time. The HUD is left at its
+/// defaults rather than loaded from `config/hud.ron`, so the frame rate never ends up in a
+/// capture.
+fn run_capture(
+    assets_dir: PathBuf,
+    bindings: Bindings<StringBindings>,
+    gameplay_config: GameplayConfig,
+    dimensions: (u32, u32),
+    render_config: &RenderConfig,
+    options: CaptureOptions,
+) -> amethyst::Result<bool> {
+    let (width, height) = dimensions;
+    let game_data = GameDataBuilder::default().with(
+        FixedTimestepSystem {
+            delta_seconds: headless::FIXED_DELTA_SECONDS,
+        },
+        "fixed_timestep",
+        &[],
+    );
+    let game_data = game::with_gameplay_systems(game_data, bindings)?;
+    let game_data = game::with_ui_systems(game_data)?.with_bundle(
+        display::offscreen_rendering_bundle(width, height, render_config),
+    )?;
+
+    let captured = Arc::new(AtomicBool::new(false));
+    let state = CaptureState::new(options, captured.clone());
+
+    // There's no window to tell the camera and the UI how big the screen is, so they're told
+    // the image's size instead
+    let mut game = Application::build(assets_dir, state)?
+        .with_resource(gameplay_config)
+        .with_resource(SimulationClock::OneTickPerFrame)
+        .with_resource(Pilot::Autopilot)
+        .with_resource(ScreenDimensions::new(width, height, 1.0))
+        .with_resource(FrameReadback::default())
+        .build(game_data)?;
+    game.run();
+
+    Ok(captured.load(Ordering::SeqCst))
+}
@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use amethyst::input::is_close_requested;
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans};
+
+use crate::attract::AttractMode;
+use crate::game::GameState;
+use crate::high_scores_menu::HighScoresState;
+use crate::menu::{Menu, MenuAction};
+use crate::options::OptionsState;
+use crate::replay::{Replay, ReplayState};
+
+const START: usize = 0;
+const HIGH_SCORES: usize = 1;
+const OPTIONS: usize = 2;
+const QUIT: usize = 3;
+
+/// The first thing the player sees. Runs are pushed on top of it, and end up back here.
+///
+/// Left alone for a while, it plays an `AttractMode` demo behind the menu.
+pub struct MainMenuState {
+    /// Seed every run starts with, if one was given on the command line or in `config/rng.ron`.
+    /// Otherwise each run gets a new random seed.
+    seed: Option<u64>,
+    /// A replay to play back (from `--replay`) before the menu is used.
+    replay: Option<Replay>,
+    menu: Option<Menu>,
+    attract: AttractMode,
+}
+
+impl MainMenuState {
+    pub fn new(seed: Option<u64>) -> Self {
+        MainMenuState {
+            seed,
+            replay: None,
+            menu: None,
+            attract: AttractMode::default(),
+        }
+    }
+
+    /// Plays `replay` back as soon as the game starts, then comes back to the menu.
+    pub fn with_replay(mut self, replay: Replay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+}
+
+impl SimpleState for MainMenuState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.menu = Some(Menu::create(
+            state_data.world,
+            "Cubefield",
+            &["Start", "High Scores", "Options", "Quit"],
+        ));
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.attract
+            .stop(StateData::new(state_data.world, state_data.data));
+        if let Some(menu) = self.menu.take() {
+            menu.delete(state_data.world);
+        }
+    }
+
+    fn on_pause(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.on_stop(state_data);
+    }
+
+    fn on_resume(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.on_start(state_data);
+    }
+
+    fn handle_event(
+        &mut self,
+        state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        if self
+            .attract
+            .handle_event(StateData::new(state_data.world, state_data.data), &event)
+        {
+            return Trans::None;
+        }
+
+        match self
+            .menu
+            .as_mut()
+            .and_then(|menu| menu.handle_event(state_data.world, &event))
+        {
+            Some(MenuAction::Confirm(START)) => {
+                let seed = self.seed.unwrap_or_else(rand::random);
+                Trans::Push(Box::new(GameState::new(seed)))
+            }
+            Some(MenuAction::Confirm(HIGH_SCORES)) => {
+                Trans::Push(Box::new(HighScoresState::default()))
+            }
+            Some(MenuAction::Confirm(OPTIONS)) => Trans::Push(Box::new(OptionsState::default())),
+            Some(MenuAction::Confirm(QUIT)) | Some(MenuAction::Back) => Trans::Quit,
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, state_data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.attract
+            .update(StateData::new(state_data.world, state_data.data));
+
+        match self.replay.take() {
+            Some(replay) => Trans::Push(Box::new(ReplayState::new(replay, Arc::default()))),
+            None => Trans::None,
+        }
+    }
+}
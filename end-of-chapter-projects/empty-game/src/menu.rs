@@ -0,0 +1,168 @@
+use amethyst::assets::{AssetStorage, Loader};
+use amethyst::ecs::{Entity, Read, ReadExpect, World};
+use amethyst::prelude::Builder;
+use amethyst::ui::{get_default_font, Anchor, FontAsset, FontHandle, UiText, UiTransform};
+use amethyst::StateEvent;
+
+use crate::controls;
+
+const TEXT_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+const SELECTED_COLOR: [f32; 4] = [0.1, 0.4, 0.9, 1.0];
+
+const TITLE_FONT_SIZE: f32 = 60.0;
+const ENTRY_FONT_SIZE: f32 = 36.0;
+const ENTRY_SPACING: f32 = 50.0;
+
+/// What the player did to a `Menu`.
+pub enum MenuAction {
+    /// The entry at this index was chosen.
+    Confirm(usize),
+    /// The player wants to leave the menu.
+    Back,
+}
+
+/// A title and a vertical list of entries, navigated with the bound menu actions.
+///
+/// "menu_up" and "menu_down" move the selection, "confirm" picks the selected entry, and "back"
+/// backs out. The menu's text entities live in the world until `delete` is called, so states should
+/// create their menu in `on_start`/`on_resume` and delete it in `on_stop`/`on_pause`.
+pub struct Menu {
+    title: Entity,
+    entries: Vec<Entity>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn create(world: &mut World, title: &str, labels: &[&str]) -> Self {
+        let font = default_font(world);
+
+        let title = create_text(
+            world,
+            &font,
+            "menu_title",
+            title,
+            ENTRY_SPACING * (labels.len() as f32 / 2.0 + 1.5),
+            TITLE_FONT_SIZE,
+        );
+
+        let entries = labels
+            .iter()
+            .enumerate()
+            .map(|(index, label)| {
+                let y = ENTRY_SPACING * (labels.len() as f32 / 2.0 - index as f32);
+                create_text(
+                    world,
+                    &font,
+                    &format!("menu_entry_{}", index),
+                    label,
+                    y,
+                    ENTRY_FONT_SIZE,
+                )
+            })
+            .collect();
+
+        let menu = Menu {
+            title,
+            entries,
+            selected: 0,
+        };
+        menu.highlight_selected(world);
+        menu
+    }
+
+    /// Index of the currently selected entry.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Changes the text of the entry at `index`.
+    pub fn set_label(&self, world: &World, index: usize, label: &str) {
+        if let Some(text) = world.write_storage::<UiText>().get_mut(self.entries[index]) {
+            text.text = label.to_string();
+        }
+    }
+
+    /// Moves the selection around, and reports when an entry was picked or the menu was backed
+    /// out of.
+    pub fn handle_event(&mut self, world: &World, event: &StateEvent) -> Option<MenuAction> {
+        match controls::action_pressed(event)? {
+            controls::MENU_UP if self.selected > 0 => {
+                self.selected -= 1;
+                self.highlight_selected(world);
+                None
+            }
+            controls::MENU_DOWN if self.selected + 1 < self.entries.len() => {
+                self.selected += 1;
+                self.highlight_selected(world);
+                None
+            }
+            controls::CONFIRM => Some(MenuAction::Confirm(self.selected)),
+            controls::BACK => Some(MenuAction::Back),
+            _ => None,
+        }
+    }
+
+    /// Removes the menu's text from the world.
+    pub fn delete(self, world: &mut World) {
+        world
+            .delete_entity(self.title)
+            .expect("Failed to delete a menu title");
+        world
+            .delete_entities(&self.entries)
+            .expect("Failed to delete menu entries");
+    }
+
+    fn highlight_selected(&self, world: &World) {
+        let mut texts = world.write_storage::<UiText>();
+        for (index, entry) in self.entries.iter().enumerate() {
+            if let Some(text) = texts.get_mut(*entry) {
+                text.color = if index == self.selected {
+                    SELECTED_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+            }
+        }
+    }
+}
+
+/// Amethyst's built-in font, used for all of the game's text.
+pub fn default_font(world: &mut World) -> FontHandle {
+    world.exec(
+        |(loader, fonts): (ReadExpect<'_, Loader>, Read<'_, AssetStorage<FontAsset>>)| {
+            get_default_font(&loader, &fonts)
+        },
+    )
+}
+
+/// Creates a line of text, centred horizontally and `y` pixels above the middle of the screen.
+pub fn create_text(
+    world: &mut World,
+    font: &FontHandle,
+    id: &str,
+    text: &str,
+    y: f32,
+    font_size: f32,
+) -> Entity {
+    let transform = UiTransform::new(
+        id.to_string(),
+        Anchor::Middle,
+        Anchor::Middle,
+        0.0,
+        y,
+        1.0,
+        800.0,
+        font_size * 1.25,
+    );
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(UiText::new(
+            font.clone(),
+            text.to_string(),
+            TEXT_COLOR,
+            font_size,
+        ))
+        .build()
+}
@@ -0,0 +1,379 @@
+use amethyst::ecs::Resources;
+use amethyst::renderer::{
+    bundle::{
+        ImageOptions, OutputColor, RenderPlan, RenderPlugin, Target, TargetImage, TargetPlanOutputs,
+    },
+    rendy::{
+        command::{
+            CommandBuffer, CommandPool, ExecutableState, Family, FamilyId, Fence, Graphics,
+            MultiShot, PendingState, Queue, SimultaneousUse, Submission, Submit, Supports,
+        },
+        factory::Factory,
+        frame::Frames,
+        graph::{
+            gfx_acquire_barriers, gfx_release_barriers, BufferAccess, BufferId, DynNode,
+            GraphContext, ImageAccess, ImageId, NodeBuffer, NodeBuilder, NodeId, NodeImage,
+        },
+        hal::{
+            self,
+            command::{ClearColor, ClearDepthStencil, ClearValue},
+        },
+        memory::Download,
+        resource::{Buffer, BufferInfo, Escape},
+    },
+    types::Backend,
+    Format, Kind,
+};
+use amethyst::Error;
+use image::RgbaImage;
+use log::error;
+
+/// Format of the image the scene is drawn into: 8-bit sRGB, which is what a PNG holds, so the
+/// pixels can be saved exactly as they're read back.
+const FORMAT: Format = Format::Rgba8Srgb;
+
+/// Bytes per pixel in `FORMAT`.
+const BYTES_PER_PIXEL: u64 = 4;
+
+/// Nothing depends on the main target when there's no window to present it to, so this target
+/// does: it exists only to pull the main target's color image into the graph, and hang the
+/// readback node off it. Its own output is a single pixel nothing ever draws to.
+const READBACK_TARGET: Target = Target::Custom("readback");
+
+/// Hands frames read back by `RenderToImage` over to the game.
+///
+/// Copying a frame back from the GPU stalls the renderer until it's done, so it only happens
+/// when one has been asked for.
+#[derive(Default)]
+pub struct FrameReadback {
+    requested: bool,
+    frame: Option<RgbaImage>,
+}
+
+impl FrameReadback {
+    /// Asks for the next frame that's rendered.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// The frame that was asked for, once it has been rendered.
+    pub fn take(&mut self) -> Option<RgbaImage> {
+        self.frame.take()
+    }
+}
+
+/// A `RenderPlugin` that draws the main target into an image of its own instead of a window,
+/// and reads it back into `FrameReadback` whenever a frame is requested there.
+///
+/// It's `RenderToWindow` for machines without a display: it needs a Vulkan driver, but a
+/// software one (Mesa's lavapipe) is enough.
+#[derive(Debug)]
+pub struct RenderToImage {
+    width: u32,
+    height: u32,
+    clear: Option<ClearColor>,
+}
+
+impl RenderToImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        RenderToImage {
+            width,
+            height,
+            clear: None,
+        }
+    }
+
+    /// Clears the image with `clear` every frame.
+    pub fn with_clear(mut self, clear: impl Into<ClearColor>) -> Self {
+        self.clear = Some(clear.into());
+        self
+    }
+}
+
+impl<B: Backend> RenderPlugin<B> for RenderToImage {
+    fn on_plan(
+        &mut self,
+        plan: &mut RenderPlan<B>,
+        _factory: &mut Factory<B>,
+        _res: &Resources,
+    ) -> Result<(), Error> {
+        let kind = Kind::D2(self.width, self.height, 1, 1);
+
+        plan.add_root(Target::Main);
+        plan.define_pass(
+            Target::Main,
+            TargetPlanOutputs {
+                colors: vec![OutputColor::Image(ImageOptions {
+                    kind,
+                    levels: 1,
+                    format: FORMAT,
+                    clear: self.clear.map(ClearValue::Color),
+                })],
+                depth: Some(ImageOptions {
+                    kind,
+                    levels: 1,
+                    format: Format::D32Sfloat,
+                    clear: Some(ClearValue::DepthStencil(ClearDepthStencil(1.0, 0))),
+                }),
+            },
+        )?;
+
+        plan.add_root(READBACK_TARGET);
+        plan.define_pass(
+            READBACK_TARGET,
+            TargetPlanOutputs {
+                colors: vec![OutputColor::Image(ImageOptions {
+                    kind: Kind::D2(1, 1, 1, 1),
+                    levels: 1,
+                    format: FORMAT,
+                    clear: None,
+                })],
+                depth: None,
+            },
+        )?;
+        plan.extend_target(READBACK_TARGET, |ctx| {
+            let image = ctx.get_image(TargetImage::Color(Target::Main, 0))?;
+            let main = ctx.get_node(Target::Main)?;
+            ctx.graph().add_node(ReadbackBuilder {
+                image,
+                dependencies: vec![main],
+            });
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+/// Builds a `ReadbackNode` for `image`.
+#[derive(Debug)]
+struct ReadbackBuilder {
+    image: ImageId,
+    dependencies: Vec<NodeId>,
+}
+
+impl<B: Backend> NodeBuilder<B, Resources> for ReadbackBuilder {
+    fn family(&self, _factory: &mut Factory<B>, families: &[Family<B>]) -> Option<FamilyId> {
+        // The same kind of queue the scene is drawn on, so the image never has to change hands
+        families
+            .iter()
+            .find(|family| Supports::<Graphics>::supports(&family.capability()).is_some())
+            .map(Family::id)
+    }
+
+    fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
+        Vec::new()
+    }
+
+    fn images(&self) -> Vec<(ImageId, ImageAccess)> {
+        vec![(
+            self.image,
+            ImageAccess {
+                access: hal::image::Access::TRANSFER_READ,
+                layout: hal::image::Layout::TransferSrcOptimal,
+                usage: hal::image::Usage::TRANSFER_SRC,
+                stages: hal::pso::PipelineStage::TRANSFER,
+            },
+        )]
+    }
+
+    fn dependencies(&self) -> Vec<NodeId> {
+        self.dependencies.clone()
+    }
+
+    fn build<'a>(
+        self: Box<Self>,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &Resources,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn DynNode<B, Resources>>, failure::Error> {
+        assert!(buffers.is_empty());
+        assert_eq!(images.len(), 1);
+
+        let image = images.into_iter().next().unwrap();
+        let extent = ctx
+            .get_image(image.id)
+            .expect("The graph is missing the node's image")
+            .kind()
+            .extent();
+
+        let buffer = factory.create_buffer(
+            BufferInfo {
+                size: u64::from(extent.width) * u64::from(extent.height) * BYTES_PER_PIXEL,
+                usage: hal::buffer::Usage::TRANSFER_DST,
+            },
+            Download,
+        )?;
+
+        let mut pool = factory.create_command_pool(family)?;
+        let (submit, command_buffer) = record_copy(ctx, &mut pool, &image, extent, &buffer);
+
+        Ok(Box::new(ReadbackNode {
+            pool,
+            submit,
+            command_buffer,
+            buffer,
+            width: extent.width,
+            height: extent.height,
+        }))
+    }
+}
+
+/// Records a command buffer copying `image` into `buffer`, reused for every frame read back.
+fn record_copy<B: Backend>(
+    ctx: &GraphContext<B>,
+    pool: &mut CommandPool<B, hal::QueueType>,
+    image: &NodeImage,
+    extent: hal::image::Extent,
+    buffer: &Buffer<B>,
+) -> (
+    Submit<B, SimultaneousUse>,
+    CommandBuffer<B, hal::QueueType, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+) {
+    let raw_image = ctx
+        .get_image(image.id)
+        .expect("The graph is missing the node's image")
+        .raw();
+
+    let mut recording = pool
+        .allocate_buffers(1)
+        .pop()
+        .unwrap()
+        .begin(MultiShot(SimultaneousUse), ());
+
+    unsafe {
+        let (stages, barriers) = gfx_acquire_barriers(ctx, None, Some(image));
+        recording
+            .encoder()
+            .pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+
+        // rendy's encoder doesn't wrap image-to-buffer copies, so this goes to gfx-hal directly
+        hal::command::RawCommandBuffer::copy_image_to_buffer(
+            recording.raw(),
+            raw_image,
+            image.layout,
+            buffer.raw(),
+            Some(hal::command::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: extent.width,
+                buffer_height: extent.height,
+                image_layers: hal::image::SubresourceLayers {
+                    aspects: hal::format::Aspects::COLOR,
+                    level: 0,
+                    layers: image.range.layers.start..image.range.layers.start + 1,
+                },
+                image_offset: hal::image::Offset::ZERO,
+                image_extent: hal::image::Extent {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                },
+            }),
+        );
+
+        let (mut stages, mut barriers) = gfx_release_barriers(ctx, None, Some(image));
+        stages.start |= hal::pso::PipelineStage::TRANSFER;
+        stages.end |= hal::pso::PipelineStage::HOST;
+        barriers.push(hal::memory::Barrier::Buffer {
+            states: hal::buffer::Access::TRANSFER_WRITE..hal::buffer::Access::HOST_READ,
+            families: None,
+            target: buffer.raw(),
+            range: None..None,
+        });
+        recording
+            .encoder()
+            .pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
+    }
+
+    recording.finish().submit()
+}
+
+/// Copies the main target's color image into a buffer the CPU can read when a frame has been
+/// requested in `FrameReadback`, and hands it over there.
+#[derive(Debug)]
+struct ReadbackNode<B: Backend> {
+    pool: CommandPool<B, hal::QueueType>,
+    submit: Submit<B, SimultaneousUse>,
+    command_buffer:
+        CommandBuffer<B, hal::QueueType, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+    buffer: Escape<Buffer<B>>,
+    width: u32,
+    height: u32,
+}
+
+impl<B: Backend> ReadbackNode<B> {
+    /// Reads the buffer back into an image. The copy into it has to have finished.
+    fn read(&mut self, factory: &Factory<B>) -> Result<RgbaImage, Error> {
+        let size = self.buffer.size();
+        let mut mapped = self
+            .buffer
+            .map(factory.device(), 0..size)
+            .map_err(|error| Error::from_string(format!("{:?}", error)))?;
+        let pixels = unsafe { mapped.read::<u8>(factory.device(), 0..size) }
+            .map_err(|error| Error::from_string(format!("{:?}", error)))?
+            .to_vec();
+
+        RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| Error::from_string("the read back frame is the wrong size"))
+    }
+}
+
+impl<B: Backend> DynNode<B, Resources> for ReadbackNode<B> {
+    unsafe fn run<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        res: &Resources,
+        _frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        let requested = res
+            .try_fetch::<FrameReadback>()
+            .map_or(false, |readback| readback.requested);
+
+        // The graph's semaphores and fence still have to be waited on and signalled when there's
+        // nothing to copy, so an empty submission stands in for the copy. Only requested frames
+        // copy into the buffer, and each waits for its copy, so no other copy is ever in flight.
+        let copy = if requested { Some(&self.submit) } else { None };
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(copy)
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter().cloned()),
+            ),
+            fence,
+        );
+
+        if !requested {
+            return;
+        }
+
+        factory.wait_idle().expect("Lost the device");
+        let mut readback = res.fetch_mut::<FrameReadback>();
+        readback.requested = false;
+        match self.read(factory) {
+            Ok(frame) => readback.frame = Some(frame),
+            Err(error) => error!("Couldn't read the frame back: {}", error),
+        }
+    }
+
+    unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &Resources) {
+        let ReadbackNode {
+            mut pool,
+            submit,
+            command_buffer,
+            ..
+        } = *self;
+        drop(submit);
+        pool.free_buffers(Some(command_buffer.mark_complete()));
+        factory.destroy_command_pool(pool);
+    }
+}
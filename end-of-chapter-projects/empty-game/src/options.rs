@@ -0,0 +1,136 @@
+use amethyst::ecs::World;
+use amethyst::input::is_close_requested;
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans};
+use log::warn;
+
+use crate::controls_menu::ControlsState;
+use crate::ghost::{GhostConfig, GhostConfigPath};
+use crate::hud::{HudConfig, HudConfigPath};
+use crate::menu::{Menu, MenuAction};
+use crate::settings;
+
+const CONTROLS: usize = 0;
+const HUD: usize = 1;
+const FPS: usize = 2;
+const GHOST: usize = 3;
+
+/// The options menu.
+#[derive(Default)]
+pub struct OptionsState {
+    menu: Option<Menu>,
+}
+
+impl OptionsState {
+    /// Flips one of the `HudConfig` settings, updates the menu to match, and saves it.
+    fn toggle_hud(&self, world: &World, setting: impl Fn(&mut HudConfig) -> &mut bool) {
+        let mut config = world.write_resource::<HudConfig>();
+        let value = setting(&mut config);
+        *value = !*value;
+
+        if let Some(menu) = &self.menu {
+            let (hud_label, fps_label) = hud_labels(&config);
+            menu.set_label(world, HUD, &hud_label);
+            menu.set_label(world, FPS, &fps_label);
+        }
+
+        let path = &world.read_resource::<HudConfigPath>().0;
+        if let Err(error) = settings::write(&*config, path) {
+            warn!("Couldn't save the HUD settings to {:?}: {}", path, error);
+        }
+    }
+
+    /// Turns the ghost on or off, updates the menu to match, and saves it.
+    fn toggle_ghost(&self, world: &World) {
+        let mut config = world.write_resource::<GhostConfig>();
+        config.enabled = !config.enabled;
+
+        if let Some(menu) = &self.menu {
+            menu.set_label(world, GHOST, &ghost_label(&config));
+        }
+
+        let path = &world.read_resource::<GhostConfigPath>().0;
+        if let Err(error) = settings::write(&*config, path) {
+            warn!("Couldn't save the ghost setting to {:?}: {}", path, error);
+        }
+    }
+}
+
+impl SimpleState for OptionsState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        let world = state_data.world;
+        let (hud_label, fps_label) = hud_labels(&world.read_resource::<HudConfig>());
+        let ghost_label = ghost_label(&world.read_resource::<GhostConfig>());
+        self.menu = Some(Menu::create(
+            world,
+            "Options",
+            &["Controls", &hud_label, &fps_label, &ghost_label, "Back"],
+        ));
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(menu) = self.menu.take() {
+            menu.delete(state_data.world);
+        }
+    }
+
+    fn on_pause(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.on_stop(state_data);
+    }
+
+    fn on_resume(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.on_start(state_data);
+    }
+
+    fn handle_event(
+        &mut self,
+        state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        match self
+            .menu
+            .as_mut()
+            .and_then(|menu| menu.handle_event(state_data.world, &event))
+        {
+            Some(MenuAction::Confirm(CONTROLS)) => Trans::Push(Box::new(ControlsState::default())),
+            Some(MenuAction::Confirm(HUD)) => {
+                self.toggle_hud(state_data.world, |config| &mut config.visible);
+                Trans::None
+            }
+            Some(MenuAction::Confirm(FPS)) => {
+                self.toggle_hud(state_data.world, |config| &mut config.show_fps);
+                Trans::None
+            }
+            Some(MenuAction::Confirm(GHOST)) => {
+                self.toggle_ghost(state_data.world);
+                Trans::None
+            }
+            Some(MenuAction::Confirm(_)) | Some(MenuAction::Back) => Trans::Pop,
+            None => Trans::None,
+        }
+    }
+}
+
+fn hud_labels(config: &HudConfig) -> (String, String) {
+    (
+        format!("HUD: {}", on_off(config.visible)),
+        format!("FPS Counter: {}", on_off(config.show_fps)),
+    )
+}
+
+fn ghost_label(config: &GhostConfig) -> String {
+    format!("Ghost: {}", on_off(config.enabled))
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "On"
+    } else {
+        "Off"
+    }
+}
@@ -0,0 +1,81 @@
+use amethyst::input::is_close_requested;
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans};
+
+use crate::controls;
+use crate::game::GameState;
+use crate::menu::{Menu, MenuAction};
+
+const RESUME: usize = 0;
+const RESTART: usize = 1;
+const QUIT_TO_MENU: usize = 2;
+
+/// Pushed on top of a `GameState` to pause the run.
+///
+/// The `GameState` underneath marks `Gameplay` as `Paused` while this is up, so the gameplay
+/// systems stop, but the scene keeps rendering behind the menu.
+pub struct PauseState {
+    /// Seed of the paused run, so "Restart" can play it again.
+    seed: u64,
+    menu: Option<Menu>,
+}
+
+impl PauseState {
+    pub fn new(seed: u64) -> Self {
+        PauseState { seed, menu: None }
+    }
+
+    /// Throws away the paused run and starts it again from the beginning, with the same seed.
+    fn restart(&self) -> SimpleTrans {
+        Trans::Sequence(vec![
+            Trans::Pop,
+            Trans::Switch(Box::new(GameState::new(self.seed))),
+        ])
+    }
+}
+
+impl SimpleState for PauseState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        self.menu = Some(Menu::create(
+            state_data.world,
+            "Paused",
+            &["Resume", "Restart", "Quit to Menu"],
+        ));
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(menu) = self.menu.take() {
+            menu.delete(state_data.world);
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        match controls::action_pressed(&event) {
+            Some(controls::PAUSE) => return Trans::Pop,
+            Some(controls::RESTART) => return self.restart(),
+            _ => {}
+        }
+
+        match self
+            .menu
+            .as_mut()
+            .and_then(|menu| menu.handle_event(state_data.world, &event))
+        {
+            Some(MenuAction::Confirm(RESUME)) | Some(MenuAction::Back) => Trans::Pop,
+            Some(MenuAction::Confirm(RESTART)) => self.restart(),
+            Some(MenuAction::Confirm(QUIT_TO_MENU)) => {
+                Trans::Sequence(vec![Trans::Pop, Trans::Pop])
+            }
+            _ => Trans::None,
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use amethyst::ecs::{Read, System, Write};
+use amethyst::input::{is_close_requested, InputHandler, StringBindings};
+use amethyst::utils::application_root_dir;
+use amethyst::{GameData, SimpleState, SimpleTrans, StateData, StateEvent, Trans};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::autopilot::{AutopilotSteering, Pilot};
+use crate::collision::ShipCrashed;
+use crate::controls;
+use crate::game::GameState;
+use crate::gameplay_config::GameplayConfig;
+use crate::score::Score;
+use crate::settings;
+
+/// Everything needed to play a run again exactly as it happened: its seed, tick rate and gameplay
+/// config, plus the steering on every tick.
+///
+/// Ticks are stored as runs of identical ticks, since the player holds the same direction for
+/// many ticks in a row. Steering is stored as a byte, and the live game steers with that same
+/// rounded value, so playing the replay back gives bit-for-bit the same run.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Replay {
+    pub seed: u64,
+    /// The `SimulationConfig::tick_rate` the run was played at.
+    pub tick_rate: u32,
+    /// The `GameplayConfig` the run was played with, including any `--difficulty` hold. The
+    /// same steering only plays out the same way with a config that `plays_like` this one.
+    pub config: GameplayConfig,
+    spans: Vec<ReplaySpan>,
+    /// How the recorded run ended, to check the playback against.
+    pub outcome: Option<ReplayOutcome>,
+}
+
+/// `ticks` ticks in a row with the same steering.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct ReplaySpan {
+    ticks: u32,
+    steer: i8,
+}
+
+/// Where and how a run ended.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReplayOutcome {
+    pub score: f32,
+    /// How many ticks were simulated, up to and including the one the ship crashed on.
+    pub crash_tick: u64,
+}
+
+impl Replay {
+    pub fn new(seed: u64, tick_rate: u32, config: GameplayConfig) -> Self {
+        Replay {
+            seed,
+            tick_rate,
+            config,
+            spans: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> amethyst::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+
+    /// Whether the run plays out the way it was recorded with `config`.
+    pub fn matches_config(&self, config: &GameplayConfig) -> bool {
+        self.config.plays_like(config)
+    }
+
+    /// Like `matches_config`, but logs why the replay can't be played if it doesn't match.
+    pub fn can_play_with(&self, config: &GameplayConfig) -> bool {
+        let matches = self.matches_config(config);
+        if !matches {
+            error!(
+                "Can't play back the replay of seed {}: it was recorded with different gameplay \
+                 settings (see assets/config/gameplay.ron, and --difficulty for headless runs)",
+                self.seed
+            );
+        }
+        matches
+    }
+
+    /// How many ticks were recorded.
+    pub fn ticks(&self) -> u64 {
+        self.spans.iter().map(|span| u64::from(span.ticks)).sum()
+    }
+
+    /// Records one more tick, steered by `steer` (from `-127`, full left, to `127`, full right).
+    pub fn push(&mut self, steer: i8) {
+        match self.spans.last_mut() {
+            Some(span) if span.steer == steer => span.ticks += 1,
+            _ => self.spans.push(ReplaySpan { ticks: 1, steer }),
+        }
+    }
+}
+
+/// Where the last run that ended in a crash is saved: `replays/last_run.ron` in the application
+/// root.
+pub fn default_path() -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?.join("replays").join("last_run.ron"))
+}
+
+/// Where the best run on `seed` is kept: `replays/best/<seed>.ron` in the application root.
+pub fn best_path(seed: u64) -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?
+        .join("replays")
+        .join("best")
+        .join(format!("{}.ron", seed)))
+}
+
+/// Loads the best run on `seed`, if one has been saved.
+///
+/// Not having one is normal (nobody has played the seed yet), so only a replay that exists but
+/// can't be read is worth a warning.
+pub fn load_best(seed: u64) -> Option<Replay> {
+    let path = best_path(seed).ok()?;
+    if !path.exists() {
+        return None;
+    }
+
+    match Replay::load(&path) {
+        Ok(replay) => Some(replay),
+        Err(error) => {
+            warn!("Couldn't load the best run from {:?}: {}", path, error);
+            None
+        }
+    }
+}
+
+/// Saves `replay` as the best run on its seed, if it scored more than the one saved already.
+/// Returns whether it did.
+///
+/// A best run played with other gameplay settings can't be raced any more, so any run replaces
+/// it.
+pub fn save_if_best(replay: &Replay) -> amethyst::Result<bool> {
+    let score = |replay: &Replay| replay.outcome.as_ref().map(|outcome| outcome.score);
+    let is_best = match load_best(replay.seed) {
+        Some(best) if !best.matches_config(&replay.config) => score(replay).is_some(),
+        Some(best) => score(replay) > score(&best),
+        None => score(replay).is_some(),
+    };
+
+    if is_best {
+        settings::write(replay, &best_path(replay.seed)?)?;
+    }
+    Ok(is_best)
+}
+
+/// Records the current run. `GameState` starts a fresh one for every run, and saves it when
+/// the ship crashes.
+#[derive(Default)]
+pub struct ReplayRecorder {
+    replay: Option<Replay>,
+}
+
+impl ReplayRecorder {
+    pub fn new(seed: u64, tick_rate: u32, config: GameplayConfig) -> Self {
+        ReplayRecorder {
+            replay: Some(Replay::new(seed, tick_rate, config)),
+        }
+    }
+
+    /// Stops recording, and returns the recording with `score` as its outcome.
+    ///
+    /// `config` is the `GameplayConfig` as of the end of the run. If it was reloaded mid-run
+    /// with changes that affect the gameplay, the recording can't be played back the same way,
+    /// so it's thrown away.
+    pub fn finish(&mut self, score: f32, config: &GameplayConfig) -> Option<Replay> {
+        let mut replay = self.replay.take()?;
+        if !replay.matches_config(config) {
+            warn!("Not keeping the replay: the gameplay config changed during the run");
+            return None;
+        }
+
+        replay.outcome = Some(ReplayOutcome {
+            score,
+            crash_tick: replay.ticks(),
+        });
+        Some(replay)
+    }
+}
+
+/// The replay being played back, if any. While one is active, it steers the ship instead of the
+/// player.
+#[derive(Default)]
+pub struct ReplayPlayback {
+    replay: Option<Replay>,
+    span: usize,
+    tick_in_span: u32,
+    ticks_played: u64,
+}
+
+impl ReplayPlayback {
+    pub fn start(&mut self, replay: Replay) {
+        *self = ReplayPlayback {
+            replay: Some(replay),
+            ..ReplayPlayback::default()
+        };
+    }
+
+    pub fn stop(&mut self) {
+        *self = ReplayPlayback::default();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// How many ticks have been played back so far.
+    pub fn ticks_played(&self) -> u64 {
+        self.ticks_played
+    }
+
+    /// Whether every recorded tick has been played back.
+    pub fn is_finished(&self) -> bool {
+        match &self.replay {
+            Some(replay) => self.span >= replay.spans.len(),
+            None => true,
+        }
+    }
+
+    /// The steering of the next recorded tick, as it should be fed to the ship; `None` once the
+    /// recording has run out.
+    pub fn next_steering(&mut self) -> Option<f32> {
+        self.next_tick().map(steer_from_byte)
+    }
+
+    /// The steering of the next recorded tick.
+    fn next_tick(&mut self) -> Option<i8> {
+        let span = self.replay.as_ref()?.spans.get(self.span)?;
+        let steer = span.steer;
+
+        self.tick_in_span += 1;
+        if self.tick_in_span >= span.ticks {
+            self.span += 1;
+            self.tick_in_span = 0;
+        }
+        self.ticks_played += 1;
+        Some(steer)
+    }
+}
+
+/// How hard the ship is being steered this tick, from `-1.0` (full left) to `1.0` (full
+/// right). `ShipControlSystem` steers with this rather than reading the input directly, so a
+/// replay can do the steering instead of the player.
+#[derive(Default)]
+pub struct Steering(pub f32);
+
+/// Fills in `Steering` every tick, either from whoever the `Pilot` is (recording it as it goes),
+/// or from the `ReplayPlayback`, if there is one.
+pub struct SteeringSystem;
+
+impl<'s> System<'s> for SteeringSystem {
+    type SystemData = (
+        Write<'s, Steering>,
+        Write<'s, ReplayRecorder>,
+        Write<'s, ReplayPlayback>,
+        Read<'s, Pilot>,
+        Read<'s, AutopilotSteering>,
+        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, ShipCrashed>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut steering,
+            mut recorder,
+            mut playback,
+            pilot,
+            autopilot,
+            input,
+            crashed,
+        ): Self::SystemData,
+    ) {
+        // Nothing after the crash is part of the run
+        if crashed.0 {
+            return;
+        }
+
+        if playback.is_active() {
+            steering.0 = playback.next_steering().unwrap_or(0.0);
+            return;
+        }
+
+        let steer = steer_to_byte(match *pilot {
+            Pilot::Player => controls::steering(&input),
+            Pilot::Autopilot => autopilot.0,
+        });
+        steering.0 = steer_from_byte(steer);
+        if let Some(replay) = &mut recorder.replay {
+            replay.push(steer);
+        }
+    }
+}
+
+fn steer_to_byte(steer: f32) -> i8 {
+    (steer.max(-1.0).min(1.0) * 127.0).round() as i8
+}
+
+fn steer_from_byte(steer: i8) -> f32 {
+    f32::from(steer) / 127.0
+}
+
+/// Plays a `Replay` back: the same run as `GameState`, but steered by the recording.
+///
+/// Once the ship crashes (or the recording runs out) the run is checked against the recorded
+/// outcome, and the state pops itself off the stack. "back" stops the replay early.
+///
+/// A replay recorded with a different `GameplayConfig` wouldn't play out the same way, so it's
+/// refused instead (and doesn't match).
+pub struct ReplayState {
+    game: GameState,
+    replay: Option<Replay>,
+    expected: Option<ReplayOutcome>,
+    /// Set if the replay can't be played with the current config.
+    refused: bool,
+    /// Shared with whoever started the replay, so they can tell whether it matched after the
+    /// state is gone.
+    matched: Arc<AtomicBool>,
+}
+
+impl ReplayState {
+    pub fn new(replay: Replay, matched: Arc<AtomicBool>) -> Self {
+        ReplayState {
+            game: GameState::new(replay.seed)
+                .with_tick_rate(replay.tick_rate)
+                .without_ghost(),
+            expected: replay.outcome.clone(),
+            replay: Some(replay),
+            refused: false,
+            matched,
+        }
+    }
+
+    /// Compares how the playback ended with how the recording did.
+    fn check(&self, actual: Option<ReplayOutcome>) {
+        let matched = actual.is_some() && actual == self.expected;
+        self.matched.store(matched, Ordering::SeqCst);
+
+        match (&actual, &self.expected) {
+            (_, None) => error!("The replay has no recorded outcome to check against"),
+            (None, Some(expected)) => error!(
+                "Replay diverged: the ship made it to the end of the recording, but when it was \
+                 recorded it crashed on tick {} with a score of {}",
+                expected.crash_tick, expected.score
+            ),
+            (Some(actual), Some(_)) if matched => info!(
+                "Replay matched: crashed on tick {} with a score of {}",
+                actual.crash_tick, actual.score
+            ),
+            (Some(actual), Some(expected)) => error!(
+                "Replay diverged: crashed on tick {} with a score of {}, but the recording \
+                 crashed on tick {} with a score of {}",
+                actual.crash_tick, actual.score, expected.crash_tick, expected.score
+            ),
+        }
+    }
+}
+
+impl SimpleState for ReplayState {
+    fn on_start(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(replay) = self.replay.take() {
+            if !replay.can_play_with(&state_data.world.read_resource::<GameplayConfig>()) {
+                self.refused = true;
+                self.matched.store(false, Ordering::SeqCst);
+                return;
+            }
+
+            info!(
+                "Playing back a replay of seed {} ({} ticks at {} per second)",
+                replay.seed,
+                replay.ticks(),
+                replay.tick_rate
+            );
+            let mut playback = ReplayPlayback::default();
+            playback.start(replay);
+            state_data.world.add_resource(playback);
+        }
+        self.game.on_start(state_data);
+    }
+
+    fn on_stop(&mut self, state_data: StateData<'_, GameData<'_, '_>>) {
+        if self.refused {
+            return;
+        }
+        state_data.world.write_resource::<ReplayPlayback>().stop();
+        self.game.on_stop(state_data);
+    }
+
+    fn handle_event(
+        &mut self,
+        _state_data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        match controls::action_pressed(&event) {
+            Some(controls::BACK) => Trans::Pop,
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, state_data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if self.refused {
+            return Trans::Pop;
+        }
+        self.game.run_simulation(state_data.world);
+
+        let world = &state_data.world;
+        let playback = world.read_resource::<ReplayPlayback>();
+
+        if world.read_resource::<ShipCrashed>().0 {
+            self.check(Some(ReplayOutcome {
+                score: world.read_resource::<Score>().current(),
+                crash_tick: playback.ticks_played(),
+            }));
+            return Trans::Pop;
+        }
+
+        if playback.is_finished() {
+            self.check(None);
+            return Trans::Pop;
+        }
+
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TestRun, MAX_TICKS};
+
+    /// A minute at the default tick rate; the autopilot might not crash for much longer.
+    const AUTOPILOT_TICKS: u64 = 3_600;
+
+    /// Records a run of up to `max_ticks` ticks, plays it back in a fresh world, and checks the
+    /// playback ends on the same tick with the same score.
+    fn assert_plays_back(seed: u64, pilot: Pilot, max_ticks: u64) -> ReplayOutcome {
+        let config = GameplayConfig::default();
+        let mut recording = TestRun::new(config.clone(), seed, pilot);
+        recording.run(max_ticks);
+        let replay = recording
+            .finish_recording()
+            .expect("The run wasn't recorded");
+        let expected = replay.outcome.clone().expect("The replay has no outcome");
+        assert_eq!(expected.crash_tick, recording.ticks());
+
+        let mut playback = TestRun::new(config, seed, Pilot::Player);
+        playback.play_back(replay);
+        playback.run(expected.crash_tick);
+
+        assert_eq!(playback.crashed(), recording.crashed());
+        assert_eq!(playback.ticks(), expected.crash_tick);
+        assert_eq!(playback.score().to_bits(), expected.score.to_bits());
+        expected
+    }
+
+    #[test]
+    fn crashing_run_plays_back_the_same() {
+        let outcome = assert_plays_back(5, Pilot::Player, MAX_TICKS);
+        assert!(outcome.crash_tick < MAX_TICKS);
+    }
+
+    #[test]
+    fn steered_run_plays_back_the_same() {
+        assert_plays_back(11, Pilot::Autopilot, AUTOPILOT_TICKS);
+    }
+
+    #[test]
+    fn spans_round_trip_through_ron() {
+        let mut replay = Replay::new(3, 60, GameplayConfig::default());
+        for steer in &[0, 0, 0, 127, 127, -64] {
+            replay.push(*steer);
+        }
+        let loaded: Replay = ron::de::from_str(&ron::ser::to_string(&replay).unwrap()).unwrap();
+
+        assert_eq!(loaded.ticks(), 6);
+        assert_eq!(loaded.spans.len(), 3);
+        assert!(loaded.matches_config(&GameplayConfig::default()));
+    }
+
+    #[test]
+    fn replay_only_matches_a_config_that_plays_the_same() {
+        let replay = Replay::new(3, 60, GameplayConfig::default());
+
+        let mut camera_moved = GameplayConfig::default();
+        camera_moved.camera.stiffness += 1.0;
+        assert!(replay.matches_config(&camera_moved));
+
+        let mut held = GameplayConfig::default();
+        held.difficulty.hold_stage("Fast").unwrap();
+        assert!(!replay.matches_config(&held));
+
+        let mut faster = GameplayConfig::default();
+        faster.cube_field.speed += 1.0;
+        assert!(!replay.matches_config(&faster));
+    }
+
+    #[test]
+    fn headless_replay_with_another_config_is_refused() {
+        let mut faster = GameplayConfig::default();
+        faster.cube_field.speed += 1.0;
+        let replay = Replay::new(3, 60, GameplayConfig::default());
+        let matched = Arc::new(AtomicBool::new(true));
+
+        let report = test_support::run_headless(
+            u64::max_value(),
+            faster,
+            Pilot::Player,
+            Box::new(ReplayState::new(replay, matched.clone())),
+        );
+
+        assert!(!matched.load(Ordering::SeqCst));
+        assert!(!report.crashed);
+    }
+
+    #[test]
+    fn recording_is_dropped_if_the_config_changed_mid_run() {
+        let mut recorder = ReplayRecorder::new(3, 60, GameplayConfig::default());
+        let mut reloaded = GameplayConfig::default();
+        reloaded.ship.max_speed += 1.0;
+
+        assert!(recorder.finish(10.0, &reloaded).is_none());
+    }
+}
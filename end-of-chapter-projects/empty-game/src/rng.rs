@@ -0,0 +1,54 @@
+use rand::{RngCore, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use serde::{Deserialize, Serialize};
+
+/// The random number generator that *all* gameplay randomness has to come from.
+///
+/// Everything random in a run is derived from a single seed, so running the game again with the
+/// same seed replays the exact same run. Don't reach for `rand::thread_rng()` in gameplay code!
+///
+/// `Pcg64Mcg` is used (instead of `StdRng`) because its output is guaranteed to never change
+/// between `rand` versions, so seeds from old bug reports keep working.
+pub struct GameRng {
+    seed: u64,
+    rng: Pcg64Mcg,
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: Pcg64Mcg::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed this run was started with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Contents of `config/rng.ron`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RngConfig {
+    /// Seed to start every run with. A random seed is picked if this is `None`.
+    pub seed: Option<u64>,
+}
@@ -0,0 +1,123 @@
+use amethyst::ecs::{Read, System, Write};
+
+use crate::autopilot::Pilot;
+use crate::collision::ShipCrashed;
+use crate::difficulty::Difficulty;
+use crate::gameplay_config::GameplayConfig;
+use crate::replay::ReplayPlayback;
+use crate::simulation::SimulationTime;
+
+/// How far the ship has made it this run, and the furthest the player has made it this session.
+#[derive(Default)]
+pub struct Score {
+    current: f32,
+    best: f32,
+}
+
+impl Score {
+    /// Distance travelled in the current run.
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    /// Furthest distance travelled in any of the player's runs since the game started, including
+    /// this one.
+    pub fn best(&self) -> f32 {
+        self.best
+    }
+
+    /// Resets the current score for a new run. The best score is kept.
+    pub fn start_run(&mut self) {
+        self.current = 0.0;
+    }
+
+    /// Adds to the current run's distance. Only runs the player is flying count towards the best
+    /// score, not the autopilot's or a replay's.
+    pub fn add_distance(&mut self, distance: f32, player_flying: bool) {
+        self.current += distance;
+        if player_flying {
+            self.best = self.best.max(self.current);
+        }
+    }
+}
+
+/// Adds the distance travelled every tick to the `Score`, until the ship crashes.
+pub struct ScoreSystem;
+
+impl<'s> System<'s> for ScoreSystem {
+    type SystemData = (
+        Write<'s, Score>,
+        Read<'s, GameplayConfig>,
+        Read<'s, Difficulty>,
+        Read<'s, ShipCrashed>,
+        Read<'s, SimulationTime>,
+        Read<'s, Pilot>,
+        Read<'s, ReplayPlayback>,
+    );
+
+    fn run(
+        &mut self,
+        (mut score, config, difficulty, crashed, time, pilot, playback): Self::SystemData,
+    ) {
+        if crashed.0 {
+            return;
+        }
+
+        score.add_distance(
+            difficulty.speed(&config.cube_field) * time.tick_seconds(),
+            *pilot == Pilot::Player && !playback.is_active(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestRun;
+
+    const TICKS: u64 = 60;
+
+    #[test]
+    fn start_run_resets_the_current_score_but_keeps_the_best() {
+        let mut score = Score::default();
+        score.add_distance(10.0, true);
+        score.start_run();
+
+        assert_eq!(score.current(), 0.0);
+        assert_eq!(score.best(), 10.0);
+    }
+
+    #[test]
+    fn best_is_the_furthest_of_the_players_runs() {
+        let mut score = Score::default();
+        for &distance in &[10.0, 25.0, 5.0] {
+            score.start_run();
+            score.add_distance(distance, true);
+        }
+        assert_eq!(score.best(), 25.0);
+
+        score.start_run();
+        score.add_distance(40.0, false);
+        assert_eq!(score.current(), 40.0);
+        assert_eq!(score.best(), 25.0);
+    }
+
+    #[test]
+    fn only_the_player_flying_moves_the_best() {
+        let mut player = TestRun::new(GameplayConfig::default(), 1, Pilot::Player);
+        player.run(TICKS);
+        assert!(player.score() > 0.0);
+        assert_eq!(player.best(), player.score());
+
+        let mut autopilot = TestRun::new(GameplayConfig::default(), 1, Pilot::Autopilot);
+        autopilot.run(TICKS);
+        assert!(autopilot.score() > 0.0);
+        assert_eq!(autopilot.best(), 0.0);
+
+        let mut replay = TestRun::new(GameplayConfig::default(), 1, Pilot::Player);
+        replay.hold_steering(0, TICKS);
+        replay.run(TICKS);
+        assert!(replay.score() > 0.0);
+        assert_eq!(replay.best(), 0.0);
+    }
+}
@@ -0,0 +1,38 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use amethyst::config::Config;
+use log::warn;
+
+/// Where the settings the player changes from the menus are saved: `settings/` in the
+/// application root.
+///
+/// The files in `config/` are the defaults, and are checked in, so the menus never write to them;
+/// they save the player's copy in here instead, which is loaded on top of the defaults.
+pub fn user_dir(app_root: &Path) -> PathBuf {
+    app_root.join("settings")
+}
+
+/// Writes `config` to `path`, creating its parent directory if needed.
+pub fn write<T: Config>(config: &T, path: &Path) -> amethyst::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    config.write(path)?;
+    Ok(())
+}
+
+/// Loads the player's copy of a config from `user_path` if they have one, or the defaults from
+/// `default_path` if they don't (or if theirs can't be read).
+pub fn load<T: Config + Default>(default_path: &Path, user_path: &Path) -> T {
+    if user_path.exists() {
+        match T::load_no_fallback(user_path) {
+            Ok(config) => return config,
+            Err(error) => warn!(
+                "Couldn't load the player's settings from {:?}, using the defaults: {}",
+                user_path, error
+            ),
+        }
+    }
+    T::load(default_path)
+}
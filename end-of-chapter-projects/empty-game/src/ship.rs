@@ -0,0 +1,213 @@
+use std::f32::consts::FRAC_PI_6;
+
+use amethyst::assets::{AssetLoaderSystemData, Handle};
+use amethyst::core::Transform;
+use amethyst::ecs::{Component, DenseVecStorage, Entity, Join, Read, System, World, WriteStorage};
+use amethyst::prelude::Builder;
+use amethyst::renderer::{
+    rendy::mesh::{Normal, Position, Tangent, TexCoord},
+    shape::Shape,
+    Material, MaterialDefaults, Mesh,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::collision::Collider;
+use crate::gameplay_config::{clamp_at_least, GameplayConfig};
+use crate::replay::Steering;
+use crate::simulation::{Interpolation, SimulationTime};
+
+/// Smallest `max_speed` and `acceleration` the config is allowed to set.
+const MIN_SHIP_SPEED: f32 = 0.01;
+
+/// The player's ship.
+///
+/// The ship never moves forwards (the cube field scrolls towards it instead), it only strafes
+/// left and right along the X axis, banking into the turn as it goes.
+#[derive(Default)]
+pub struct Ship {
+    /// Current sideways velocity, in units per second. Positive is to the right.
+    pub velocity: f32,
+}
+
+impl Ship {
+    /// How far the ship rolls around the Z axis for its sideways velocity; banking right means a
+    /// clockwise (negative) roll. The camera rolls along with this too.
+    pub fn bank(&self, config: &ShipConfig) -> f32 {
+        if config.max_speed > 0.0 {
+            -self.velocity / config.max_speed * config.max_bank_angle
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Component for Ship {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// How the ship handles, loaded as part of `GameplayConfig`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShipConfig {
+    /// Fastest the ship can strafe sideways, in units per second.
+    pub max_speed: f32,
+    /// How quickly the ship speeds up and slows down sideways, in units per second squared.
+    pub acceleration: f32,
+    /// How far the ship rolls (in radians) when it's strafing at `max_speed`.
+    pub max_bank_angle: f32,
+    /// Furthest the ship can strafe from the middle of the cube field, either way. This should
+    /// stay inside the field's spread at every difficulty stage, or the ship can fly out to
+    /// where no cubes spawn.
+    pub max_x: f32,
+}
+
+impl ShipConfig {
+    /// The ship has to be able to move: a `max_speed` or `acceleration` of zero would make its
+    /// bank (and the autopilot's steering) divide by zero.
+    pub fn validate(&mut self) {
+        clamp_at_least("ship.max_speed", &mut self.max_speed, MIN_SHIP_SPEED);
+        clamp_at_least("ship.acceleration", &mut self.acceleration, MIN_SHIP_SPEED);
+        clamp_at_least("ship.max_x", &mut self.max_x, 0.0);
+    }
+}
+
+impl Default for ShipConfig {
+    fn default() -> Self {
+        ShipConfig {
+            max_speed: 15.0,
+            acceleration: 60.0,
+            max_bank_angle: FRAC_PI_6,
+            max_x: 20.0,
+        }
+    }
+}
+
+/// Creates the ship at the origin. It only gets a mesh and material if `render` is set.
+pub fn initialize_ship(world: &mut World, render: bool) -> Entity {
+    let render_assets = if render {
+        Some(load_ship_assets(world))
+    } else {
+        None
+    };
+
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(0.0, 0.0, 0.0);
+
+    let mut ship = world
+        .create_entity()
+        .with(Ship::default())
+        .with(Collider::new(0.5, 0.2, 1.0))
+        .with(Interpolation::new(&transform))
+        .with(transform);
+    if let Some((mesh, material)) = render_assets {
+        ship = ship.with(mesh).with(material);
+    }
+    ship.build()
+}
+
+fn load_ship_assets(world: &mut World) -> (Handle<Mesh>, Handle<Material>) {
+    let mesh = load_ship_mesh(world);
+
+    let material_defaults = world.read_resource::<MaterialDefaults>().0.clone();
+    let material = world.exec(|loader: AssetLoaderSystemData<'_, Material>| {
+        loader.load_from_data(
+            Material {
+                ..material_defaults
+            },
+            (),
+        )
+    });
+
+    (mesh, material)
+}
+
+/// The ship is just a squashed sphere for now: wide, flat, and long.
+pub fn load_ship_mesh(world: &mut World) -> Handle<Mesh> {
+    world.exec(|loader: AssetLoaderSystemData<'_, Mesh>| {
+        loader.load_from_data(
+            Shape::Sphere(32, 32)
+                .generate::<(Vec<Position>, Vec<Normal>, Vec<Tangent>, Vec<TexCoord>)>(Some((
+                    0.5, 0.2, 1.0,
+                )))
+                .into(),
+            (),
+        )
+    })
+}
+
+/// Strafes and banks the ship based on the `Steering`.
+pub struct ShipControlSystem;
+
+impl<'s> System<'s> for ShipControlSystem {
+    type SystemData = (
+        WriteStorage<'s, Ship>,
+        WriteStorage<'s, Transform>,
+        Read<'s, Steering>,
+        Read<'s, GameplayConfig>,
+        Read<'s, SimulationTime>,
+    );
+
+    fn run(&mut self, (mut ships, mut transforms, steering, config, time): Self::SystemData) {
+        for (ship, transform) in (&mut ships, &mut transforms).join() {
+            steer_ship(
+                ship,
+                transform,
+                steering.0,
+                &config.ship,
+                time.tick_seconds(),
+            );
+        }
+    }
+}
+
+/// Moves `ship` through one tick of `delta_seconds`, steering it by `steer` (from `-1.0` to
+/// `1.0`, like `Steering`). The ship stops dead if it reaches `max_x`.
+///
+/// Anything flying like the ship (the ghost of a previous run, say) has to move with this too,
+/// so it handles exactly the same.
+pub fn steer_ship(
+    ship: &mut Ship,
+    transform: &mut Transform,
+    steer: f32,
+    config: &ShipConfig,
+    delta_seconds: f32,
+) {
+    ship.velocity = approach(
+        ship.velocity,
+        steer * config.max_speed,
+        config.acceleration * delta_seconds,
+    );
+    transform.prepend_translation_x(ship.velocity * delta_seconds);
+
+    let x = transform.translation().x;
+    if x.abs() > config.max_x {
+        transform.set_translation_x(x.max(-config.max_x).min(config.max_x));
+        ship.velocity = 0.0;
+    }
+    transform.set_rotation_euler(0.0, 0.0, ship.bank(config));
+}
+
+/// Moves `current` towards `target` by at most `max_step`.
+fn approach(current: f32, target: f32, max_step: f32) -> f32 {
+    if current < target {
+        (current + max_step).min(target)
+    } else {
+        (current - max_step).max(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::autopilot::Pilot;
+    use crate::gameplay_config::GameplayConfig;
+    use crate::test_support::{TestRun, MAX_TICKS};
+
+    #[test]
+    fn holding_one_way_doesnt_escape_the_cube_field() {
+        let mut run = TestRun::new(GameplayConfig::default(), 5, Pilot::Player);
+        run.hold_steering(127, MAX_TICKS);
+        run.run(MAX_TICKS);
+
+        assert!(run.crashed());
+    }
+}
@@ -0,0 +1,286 @@
+use amethyst::core::{ArcThreadPool, Time, Transform};
+use amethyst::ecs::{
+    Component, DenseVecStorage, Dispatcher, DispatcherBuilder, Join, Read, ReadStorage, System,
+    World, WriteStorage,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::autopilot::AutopilotSystem;
+use crate::collision::{CollisionSystem, ShipCrashed};
+use crate::cube_field::CubeFieldSystem;
+use crate::difficulty::DifficultySystem;
+use crate::ghost::GhostSystem;
+use crate::replay::SteeringSystem;
+use crate::score::ScoreSystem;
+use crate::ship::ShipControlSystem;
+
+/// Most ticks a single frame will catch up on. If the game falls further behind than this (say,
+/// after the window was dragged around), the simulation slows down instead of freezing while it
+/// catches up.
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+/// How the simulation runs, loaded as part of `GameplayConfig`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    /// Simulation ticks per second. This is read at the start of each run, so changing it only
+    /// affects the next run.
+    pub tick_rate: u32,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig { tick_rate: 60 }
+    }
+}
+
+/// How the simulation keeps time with the frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationClock {
+    /// However many ticks fit into the frame's real time, so the game runs at the same speed at
+    /// any frame rate.
+    RealTime,
+    /// Exactly one tick per frame, however long the frame took. Headless runs and captures use
+    /// this, so they simulate the same ticks no matter how fast the machine is.
+    OneTickPerFrame,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        SimulationClock::RealTime
+    }
+}
+
+/// The simulation's clock. Gameplay systems step by `tick_seconds` instead of the frame's
+/// `Time::delta_seconds`, which is what makes runs (and replays) reproducible.
+#[derive(Default)]
+pub struct SimulationTime {
+    tick_seconds: f32,
+    /// Real time that has passed but hasn't been simulated yet.
+    accumulator: f32,
+    ticks: u64,
+}
+
+impl SimulationTime {
+    pub fn new(tick_rate: u32) -> Self {
+        SimulationTime {
+            tick_seconds: 1.0 / tick_rate.max(1) as f32,
+            accumulator: 0.0,
+            ticks: 0,
+        }
+    }
+
+    /// How much time every tick simulates, in seconds.
+    pub fn tick_seconds(&self) -> f32 {
+        self.tick_seconds
+    }
+
+    /// How many ticks have been simulated this run.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// How far the current frame is between the last two ticks, from `0.0` to `1.0`; what
+    /// `InterpolationSystem` blends their transforms by.
+    pub fn alpha(&self) -> f32 {
+        if self.tick_seconds > 0.0 {
+            (self.accumulator / self.tick_seconds).min(1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// The gameplay systems (the autopilot, steering, movement, spawning, collision and scoring,
+/// plus the ghost), in a dispatcher of their own so they can run at a fixed tick rate instead of
+/// once per frame.
+///
+/// `GameState` creates one for each run, and runs it from its `update`. Since that only happens
+/// while the `GameState` is on top of the stack, pausing the game pauses the simulation too.
+pub struct Simulation {
+    dispatcher: Dispatcher<'static, 'static>,
+}
+
+impl Simulation {
+    pub fn new(world: &mut World) -> Self {
+        // Tests run the simulation in a bare `World`, without the `Application`'s thread pool
+        let mut builder = DispatcherBuilder::new();
+        if let Some(pool) = world.res.try_fetch::<ArcThreadPool>() {
+            builder = builder.with_pool(pool.clone());
+        }
+
+        let mut dispatcher = builder
+            .with(BeginTickSystem, "begin_tick", &[])
+            .with(AutopilotSystem, "autopilot", &["begin_tick"])
+            .with(SteeringSystem, "steering", &["autopilot"])
+            .with(ShipControlSystem, "ship_control", &["steering"])
+            .with(GhostSystem, "ghost", &["begin_tick"])
+            .with(DifficultySystem, "difficulty", &["begin_tick"])
+            .with(CubeFieldSystem, "cube_field", &["difficulty"])
+            .with(
+                CollisionSystem,
+                "collision",
+                &["ship_control", "cube_field"],
+            )
+            .with(ScoreSystem, "score", &["collision"])
+            .with(EndTickSystem, "end_tick", &["collision", "ghost"])
+            .build();
+        dispatcher.setup(&mut world.res);
+
+        Simulation { dispatcher }
+    }
+
+    /// Runs however many ticks are due this frame, stopping early if the ship crashes.
+    pub fn run(&mut self, world: &mut World) {
+        let ticks = {
+            let clock = *world.read_resource::<SimulationClock>();
+            let frame_seconds = world.read_resource::<Time>().delta_seconds();
+            let mut time = world.write_resource::<SimulationTime>();
+            match clock {
+                SimulationClock::RealTime => {
+                    time.accumulator += frame_seconds;
+                    let due = (time.accumulator / time.tick_seconds) as u32;
+                    let ticks = due.min(MAX_TICKS_PER_FRAME);
+                    time.accumulator = if due > ticks {
+                        0.0
+                    } else {
+                        time.accumulator - ticks as f32 * time.tick_seconds
+                    };
+                    ticks
+                }
+                SimulationClock::OneTickPerFrame => {
+                    time.accumulator = time.tick_seconds;
+                    1
+                }
+            }
+        };
+
+        for _ in 0..ticks {
+            if world.read_resource::<ShipCrashed>().0 {
+                break;
+            }
+            self.tick(world);
+        }
+    }
+
+    /// Runs a single tick, whether it's due or not.
+    pub fn tick(&mut self, world: &mut World) {
+        self.dispatcher.dispatch(&world.res);
+        world.maintain();
+        world.write_resource::<SimulationTime>().ticks += 1;
+    }
+}
+
+/// An entity's `Transform` as of the last two ticks.
+///
+/// Between ticks, the `Transform` is blended between them so movement looks smooth at any frame
+/// rate, while `current` keeps the simulation's real state.
+pub struct Interpolation {
+    previous: Transform,
+    current: Transform,
+}
+
+impl Interpolation {
+    /// For an entity that starts out at `transform`.
+    pub fn new(transform: &Transform) -> Self {
+        Interpolation {
+            previous: transform.clone(),
+            current: transform.clone(),
+        }
+    }
+}
+
+impl Component for Interpolation {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// First in every tick: puts back the simulation's real transforms (replacing the blended ones
+/// that were last drawn), and remembers them as the "previous" ones.
+struct BeginTickSystem;
+
+impl<'s> System<'s> for BeginTickSystem {
+    type SystemData = (WriteStorage<'s, Interpolation>, WriteStorage<'s, Transform>);
+
+    fn run(&mut self, (mut interpolations, mut transforms): Self::SystemData) {
+        for (interpolation, transform) in (&mut interpolations, &mut transforms).join() {
+            *transform = interpolation.current.clone();
+            interpolation.previous = interpolation.current.clone();
+        }
+    }
+}
+
+/// Last in every tick: remembers the transforms the tick ended up with.
+struct EndTickSystem;
+
+impl<'s> System<'s> for EndTickSystem {
+    type SystemData = (WriteStorage<'s, Interpolation>, ReadStorage<'s, Transform>);
+
+    fn run(&mut self, (mut interpolations, transforms): Self::SystemData) {
+        for (interpolation, transform) in (&mut interpolations, &transforms).join() {
+            interpolation.current = transform.clone();
+        }
+    }
+}
+
+/// Blends every interpolated `Transform` between its last two ticks, for drawing.
+pub struct InterpolationSystem;
+
+impl<'s> System<'s> for InterpolationSystem {
+    type SystemData = (
+        Read<'s, SimulationTime>,
+        ReadStorage<'s, Interpolation>,
+        WriteStorage<'s, Transform>,
+    );
+
+    fn run(&mut self, (time, interpolations, mut transforms): Self::SystemData) {
+        let alpha = time.alpha();
+        for (interpolation, transform) in (&interpolations, &mut transforms).join() {
+            let (previous, current) = (&interpolation.previous, &interpolation.current);
+            transform.set_translation(previous.translation().lerp(current.translation(), alpha));
+            transform.set_rotation(previous.rotation().slerp(current.rotation(), alpha));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autopilot::Pilot;
+    use crate::gameplay_config::GameplayConfig;
+    use crate::test_support::TestRun;
+
+    /// A power of two, so ticks and the frames below add up exactly in an `f32`.
+    const TICK_RATE: u32 = 64;
+
+    fn run() -> TestRun {
+        let mut config = GameplayConfig::default();
+        config.simulation.tick_rate = TICK_RATE;
+        TestRun::new(config, 1, Pilot::Autopilot)
+    }
+
+    #[test]
+    fn real_time_runs_the_ticks_that_fit_in_each_frame() {
+        let mut run = run();
+        run.run_frames(SimulationClock::RealTime, 10, 2.0 / TICK_RATE as f32);
+        assert_eq!(run.ticks(), 20);
+
+        // Half a tick per frame only runs a tick every other frame
+        run.run_frames(SimulationClock::RealTime, 4, 0.5 / TICK_RATE as f32);
+        assert_eq!(run.ticks(), 22);
+    }
+
+    #[test]
+    fn real_time_catches_up_on_at_most_max_ticks_per_frame() {
+        let mut run = run();
+        run.run_frames(SimulationClock::RealTime, 1, 1.0);
+        assert_eq!(run.ticks(), u64::from(MAX_TICKS_PER_FRAME));
+    }
+
+    #[test]
+    fn one_tick_per_frame_ignores_the_frame_time() {
+        let mut run = run();
+        run.run_frames(SimulationClock::OneTickPerFrame, 5, 1.0);
+        run.run_frames(SimulationClock::OneTickPerFrame, 5, 0.0);
+        assert_eq!(run.ticks(), 10);
+    }
+}
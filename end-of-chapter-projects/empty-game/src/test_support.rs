@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use amethyst::core::Time;
+use amethyst::ecs::World;
+use amethyst::input::{Bindings, StringBindings};
+use amethyst::{GameData, State, StateEvent};
+
+use crate::autopilot::Pilot;
+use crate::collision::ShipCrashed;
+use crate::game;
+use crate::gameplay_config::GameplayConfig;
+use crate::headless::HeadlessReport;
+use crate::replay::{Replay, ReplayPlayback, ReplayRecorder};
+use crate::score::Score;
+use crate::simulation::{Simulation, SimulationClock, SimulationTime};
+
+/// Long enough for a run nobody steers to crash: the ship flies straight ahead, and sooner or
+/// later a cube spawns in its way.
+pub const MAX_TICKS: u64 = 36_000;
+
+/// A run simulated directly in a bare `World`: the same `Simulation` a `GameState` runs, but
+/// without an `Application`, states, a window or any rendering, ticking as fast as it can (or
+/// frame by frame, with `run_frames`).
+pub struct TestRun {
+    world: World,
+    simulation: Simulation,
+}
+
+impl TestRun {
+    /// A fresh run on `seed` with `config`, flown by `pilot`.
+    pub fn new(config: GameplayConfig, seed: u64, pilot: Pilot) -> Self {
+        let mut world = World::new();
+        let tick_rate = config.simulation.tick_rate;
+        world.add_resource(config);
+        world.add_resource(pilot);
+        world.add_resource(Time::default());
+
+        let (simulation, _) = game::start_run(&mut world, seed, tick_rate, false);
+        TestRun { world, simulation }
+    }
+
+    /// Steers with `replay` from now on, instead of the pilot.
+    pub fn play_back(&mut self, replay: Replay) {
+        self.world.write_resource::<ReplayPlayback>().start(replay);
+    }
+
+    /// Runs ticks until the ship crashes, or until `max_ticks` ticks have been run in total.
+    pub fn run(&mut self, max_ticks: u64) {
+        while self.ticks() < max_ticks && !self.crashed() {
+            self.simulation.tick(&mut self.world);
+        }
+    }
+
+    /// Runs `frames` frames of `frame_seconds` each through `Simulation::run`, the way a
+    /// `GameState` does every frame, with `clock` keeping time.
+    pub fn run_frames(&mut self, clock: SimulationClock, frames: u32, frame_seconds: f32) {
+        self.world.add_resource(clock);
+        for _ in 0..frames {
+            self.world
+                .write_resource::<Time>()
+                .set_delta_seconds(frame_seconds);
+            self.simulation.run(&mut self.world);
+        }
+    }
+
+    /// Steers with `steer` (from `-127`, full left, to `127`, full right) for the next
+    /// `ticks` ticks, instead of the pilot.
+    pub fn hold_steering(&mut self, steer: i8, ticks: u64) {
+        let mut replay = {
+            let config = self.world.read_resource::<GameplayConfig>();
+            Replay::new(0, config.simulation.tick_rate, config.clone())
+        };
+        for _ in 0..ticks {
+            replay.push(steer);
+        }
+        self.play_back(replay);
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.world.read_resource::<SimulationTime>().ticks()
+    }
+
+    pub fn crashed(&self) -> bool {
+        self.world.read_resource::<ShipCrashed>().0
+    }
+
+    pub fn score(&self) -> f32 {
+        self.world.read_resource::<Score>().current()
+    }
+
+    pub fn best(&self) -> f32 {
+        self.world.read_resource::<Score>().best()
+    }
+
+    /// Stops recording, and returns the recording of the run so far.
+    pub fn finish_recording(&mut self) -> Option<Replay> {
+        let score = self.score();
+        let config = self.world.read_resource::<GameplayConfig>();
+        self.world
+            .write_resource::<ReplayRecorder>()
+            .finish(score, &config)
+    }
+}
+
+/// Runs `run` for up to `frames` frames with `run_headless`, flown by `pilot`, with the game's
+/// own assets, and returns its report.
+pub fn run_headless(
+    frames: u64,
+    config: GameplayConfig,
+    pilot: Pilot,
+    run: Box<dyn State<GameData<'static, 'static>, StateEvent>>,
+) -> HeadlessReport {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    crate::run_headless(
+        root.join("assets"),
+        Bindings::<StringBindings>::default(),
+        config,
+        pilot,
+        frames,
+        run,
+    )
+    .expect("The headless run failed")
+}